@@ -0,0 +1,1008 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{from_json, to_json_binary, Addr, Binary, ConversionOverflowError, Decimal256, Deps, DepsMut, Env, Int128, MessageInfo, Order, Response, StdError, StdResult, Storage, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_storage_plus::Bound;
+use cw_utils::nonpayable;
+use dex::asset::{assert_expected_funds, assert_offer_funds, native_received, recipient, Asset, AssetInfo};
+
+use crate::error::ContractError;
+use crate::math::{self, SqrtPrice, MAX_TICK, MIN_TICK};
+use crate::msg::{ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, PoolResponse, PositionResponse, PositionsResponse, QueryMsg, SimulationResponse, TickResponse};
+use crate::state::{Config, PoolState, Position, TickInfo, CONFIG, DEPOSITS, POOL, POSITIONS, TICKS};
+
+pub const MAX_FEE_BPS: u16 = 100;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    for asset_info in msg.asset_infos.iter() {
+        asset_info.check(deps.api)?;
+    }
+    if msg.asset_infos[0] == msg.asset_infos[1] {
+        return Err(ContractError::InvalidTokenPair {});
+    }
+    if msg.tick_spacing <= 0 {
+        return Err(ContractError::InvalidTickSpacing {});
+    }
+    if msg.fee_bps > MAX_FEE_BPS {
+        return Err(ContractError::InvalidFee { fee_bps: msg.fee_bps, max_fee_bps: MAX_FEE_BPS });
+    }
+
+    let sqrt_price = SqrtPrice::from_price(msg.initial_price)?;
+    let tick = math::tick_at_sqrt_price(sqrt_price)?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            asset_infos: msg.asset_infos,
+            tick_spacing: msg.tick_spacing,
+            fee_bps: msg.fee_bps,
+        },
+    )?;
+    POOL.save(
+        deps.storage,
+        &PoolState {
+            sqrt_price,
+            tick,
+            liquidity: Uint128::zero(),
+            fee_growth_global: [Decimal256::zero(); 2],
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("sqrt_price", sqrt_price.to_string())
+        .add_attribute("tick", tick.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Receive(cw20_msg) => receive_cw20(deps, env, info, cw20_msg),
+        ExecuteMsg::ProvideLiquidity { lower_tick, upper_tick, assets, min_liquidity } => {
+            provide_liquidity(deps, info, lower_tick, upper_tick, assets, min_liquidity)
+        }
+        ExecuteMsg::WithdrawLiquidity { lower_tick, upper_tick, liquidity, to } => {
+            nonpayable(&info)?;
+            let receiver = recipient(deps.api, to, &info.sender)?;
+            withdraw_liquidity(deps, info.sender, receiver, lower_tick, upper_tick, liquidity)
+        }
+        ExecuteMsg::CollectFees { lower_tick, upper_tick, to } => {
+            nonpayable(&info)?;
+            let receiver = recipient(deps.api, to, &info.sender)?;
+            collect_fees(deps, info.sender, receiver, lower_tick, upper_tick)
+        }
+        ExecuteMsg::Swap { offer_asset, min_output, to } => {
+            if !offer_asset.info.is_native_token() {
+                return Err(ContractError::Cw20OfferNotReceived { asset: offer_asset.info.to_string() });
+            }
+            assert_offer_funds(&offer_asset.info, &info)?;
+            let received = native_received(&info, &offer_asset.info);
+            if received < offer_asset.amount {
+                return Err(ContractError::InsufficientFunds {
+                    asset: offer_asset.info.to_string(),
+                    expected: offer_asset.amount,
+                    received,
+                });
+            }
+            let receiver = recipient(deps.api, to, &info.sender)?;
+            let excess = Asset { info: offer_asset.info.clone(), amount: received - offer_asset.amount };
+            let mut response = swap(deps, info.sender.clone(), receiver, offer_asset, min_output)?;
+            if !excess.amount.is_zero() {
+                response = response.add_message(excess.transfer_msg(&info.sender)?);
+            }
+            Ok(response)
+        }
+        ExecuteMsg::WithdrawDeposits {} => {
+            nonpayable(&info)?;
+            withdraw_deposits(deps, info.sender)
+        }
+    }
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+    // info.sender is the cw20 contract that forwarded the tokens
+    let asset = Asset {
+        info: AssetInfo::Token { contract_addr: info.sender.to_string() },
+        amount: cw20_msg.amount,
+    };
+    asset_index(&config, &asset.info)?;
+
+    match from_json(&cw20_msg.msg)? {
+        Cw20HookMsg::Swap { min_output, to } => {
+            let receiver = recipient(deps.api, to, &sender)?;
+            swap(deps, sender, receiver, asset, min_output)
+        }
+        Cw20HookMsg::Deposit {} => {
+            if asset.amount.is_zero() {
+                return Err(ContractError::ZeroAmount {});
+            }
+            let balance = DEPOSITS.update(deps.storage, (&sender, info.sender.as_str()), |balance| -> StdResult<_> {
+                Ok(balance.unwrap_or_default().checked_add(asset.amount)?)
+            })?;
+            Ok(Response::new()
+                .add_attribute("method", "deposit")
+                .add_attribute("sender", sender)
+                .add_attribute("asset", asset.to_string())
+                .add_attribute("deposit_balance", balance))
+        }
+    }
+}
+
+fn asset_index(config: &Config, asset_info: &AssetInfo) -> Result<usize, ContractError> {
+    config
+        .asset_infos
+        .iter()
+        .position(|info| info == asset_info)
+        .ok_or(ContractError::UnsupportedToken { asset: asset_info.to_string() })
+}
+
+fn assert_tick_range(
+    config: &Config,
+    lower_tick: i32,
+    upper_tick: i32,
+) -> Result<(), ContractError> {
+    if lower_tick >= upper_tick
+        || lower_tick < MIN_TICK
+        || upper_tick > MAX_TICK
+        || lower_tick % config.tick_spacing != 0
+        || upper_tick % config.tick_spacing != 0
+    {
+        return Err(ContractError::InvalidTickRange { lower_tick, upper_tick, min_tick: MIN_TICK, max_tick: MAX_TICK });
+    }
+    Ok(())
+}
+
+// Fee growth values only matter through their differences, so they wrap around like Uniswap's
+fn growth_add(a: Decimal256, b: Decimal256) -> Decimal256 {
+    Decimal256::new(a.atomics().wrapping_add(b.atomics()))
+}
+
+fn growth_sub(a: Decimal256, b: Decimal256) -> Decimal256 {
+    Decimal256::new(a.atomics().wrapping_sub(b.atomics()))
+}
+
+fn signed_liquidity(liquidity: Uint128) -> Result<i128, ContractError> {
+    i128::try_from(liquidity.u128()).map_err(|_| ConversionOverflowError::new("Uint128", "Int128", liquidity.to_string()).into())
+}
+
+fn apply_liquidity_delta(liquidity: Uint128, delta: i128) -> Result<Uint128, ContractError> {
+    let magnitude = Uint128::new(delta.unsigned_abs());
+    if delta >= 0 {
+        Ok(liquidity.checked_add(magnitude)?)
+    } else {
+        Ok(liquidity.checked_sub(magnitude)?)
+    }
+}
+
+// An uninitialized tick takes all the fee growth so far as below it when the price is above it
+fn load_tick(
+    storage: &dyn Storage,
+    pool: &PoolState,
+    tick: i32,
+) -> StdResult<TickInfo> {
+    Ok(TICKS.may_load(storage, tick)?.unwrap_or_else(|| TickInfo {
+        liquidity_gross: Uint128::zero(),
+        liquidity_net: Int128::zero(),
+        fee_growth_outside: if tick <= pool.tick { pool.fee_growth_global } else { [Decimal256::zero(); 2] },
+    }))
+}
+
+fn fee_growth_inside(
+    pool: &PoolState,
+    lower_tick: i32,
+    lower: &TickInfo,
+    upper_tick: i32,
+    upper: &TickInfo,
+) -> [Decimal256; 2] {
+    [0, 1].map(|i| {
+        let global = pool.fee_growth_global[i];
+        let below = if pool.tick >= lower_tick { lower.fee_growth_outside[i] } else { growth_sub(global, lower.fee_growth_outside[i]) };
+        let above = if pool.tick < upper_tick { upper.fee_growth_outside[i] } else { growth_sub(global, upper.fee_growth_outside[i]) };
+        growth_sub(growth_sub(global, below), above)
+    })
+}
+
+// Fees earned by the position since its last update, not yet in tokens_owed
+fn fees_earned(
+    position: &Position,
+    fee_growth_inside: &[Decimal256; 2],
+) -> Result<[Uint128; 2], ContractError> {
+    Ok([
+        math::mul_growth(position.liquidity, growth_sub(fee_growth_inside[0], position.fee_growth_inside_last[0]))?,
+        math::mul_growth(position.liquidity, growth_sub(fee_growth_inside[1], position.fee_growth_inside_last[1]))?,
+    ])
+}
+
+// Credits the fees earned so far to the position, then adds liquidity_delta to it, to its ticks and,
+// when the range contains the current price, to the pool. The position is returned unsaved.
+fn update_position(
+    storage: &mut dyn Storage,
+    pool: &mut PoolState,
+    owner: &Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+    liquidity_delta: i128,
+) -> Result<Position, ContractError> {
+    let mut lower = load_tick(storage, pool, lower_tick)?;
+    let mut upper = load_tick(storage, pool, upper_tick)?;
+    let inside = fee_growth_inside(pool, lower_tick, &lower, upper_tick, &upper);
+
+    let mut position = POSITIONS.may_load(storage, (owner, lower_tick, upper_tick))?.unwrap_or(Position {
+        liquidity: Uint128::zero(),
+        fee_growth_inside_last: inside,
+        tokens_owed: [Uint128::zero(); 2],
+    });
+    let earned = fees_earned(&position, &inside)?;
+    position.tokens_owed[0] = position.tokens_owed[0].checked_add(earned[0])?;
+    position.tokens_owed[1] = position.tokens_owed[1].checked_add(earned[1])?;
+    position.fee_growth_inside_last = inside;
+    position.liquidity = apply_liquidity_delta(position.liquidity, liquidity_delta)?;
+
+    let gross_delta = Uint128::new(liquidity_delta.unsigned_abs());
+    for (tick, info, net_delta) in [(lower_tick, &mut lower, liquidity_delta), (upper_tick, &mut upper, -liquidity_delta)] {
+        info.liquidity_gross = if liquidity_delta >= 0 { info.liquidity_gross.checked_add(gross_delta)? } else { info.liquidity_gross.checked_sub(gross_delta)? };
+        info.liquidity_net = info.liquidity_net.checked_add(Int128::new(net_delta))?;
+        if info.liquidity_gross.is_zero() {
+            TICKS.remove(storage, tick);
+        } else {
+            TICKS.save(storage, tick, info)?;
+        }
+    }
+
+    if lower_tick <= pool.tick && pool.tick < upper_tick {
+        pool.liquidity = apply_liquidity_delta(pool.liquidity, liquidity_delta)?;
+    }
+
+    Ok(position)
+}
+
+fn save_position(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+    position: &Position,
+) -> StdResult<()> {
+    let key = (owner, lower_tick, upper_tick);
+    if position.liquidity.is_zero() && position.tokens_owed.iter().all(|owed| owed.is_zero()) {
+        POSITIONS.remove(storage, key);
+        Ok(())
+    } else {
+        POSITIONS.save(storage, key, position)
+    }
+}
+
+pub fn provide_liquidity(
+    deps: DepsMut,
+    info: MessageInfo,
+    lower_tick: i32,
+    upper_tick: i32,
+    assets: [Asset; 2],
+    min_liquidity: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    assert_tick_range(&config, lower_tick, upper_tick)?;
+    assert_expected_funds(&config.asset_infos, &info)?;
+
+    let mut amounts = [Uint128::zero(); 2];
+    let index_0 = asset_index(&config, &assets[0].info)?;
+    let index_1 = asset_index(&config, &assets[1].info)?;
+    if index_0 == index_1 {
+        return Err(ContractError::InvalidTokenPair {});
+    }
+    amounts[index_0] = assets[0].amount;
+    amounts[index_1] = assets[1].amount;
+
+    // native funds come with the message, cw20 tokens from the sender's deposits
+    let mut available = [Uint128::zero(); 2];
+    for (i, asset_info) in config.asset_infos.iter().enumerate() {
+        available[i] = match asset_info {
+            AssetInfo::NativeToken { .. } => native_received(&info, asset_info),
+            AssetInfo::Token { contract_addr } => DEPOSITS.may_load(deps.storage, (&info.sender, contract_addr.as_str()))?.unwrap_or_default(),
+        };
+        if available[i] < amounts[i] {
+            return Err(ContractError::InsufficientFunds {
+                asset: asset_info.to_string(),
+                expected: amounts[i],
+                received: available[i],
+            });
+        }
+    }
+
+    let mut pool = POOL.load(deps.storage)?;
+    let sqrt_price_lower = math::sqrt_price_at_tick(lower_tick)?;
+    let sqrt_price_upper = math::sqrt_price_at_tick(upper_tick)?;
+    let liquidity = math::liquidity_for_amounts(pool.sqrt_price, sqrt_price_lower, sqrt_price_upper, &amounts)?;
+    if liquidity.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if let Some(min_liquidity) = min_liquidity {
+        if liquidity < min_liquidity {
+            return Err(ContractError::MinLiquidityNotReached { min_liquidity, liquidity });
+        }
+    }
+    let used = math::amounts_for_liquidity(pool.sqrt_price, sqrt_price_lower, sqrt_price_upper, liquidity, true)?;
+
+    let position = update_position(deps.storage, &mut pool, &info.sender, lower_tick, upper_tick, signed_liquidity(liquidity)?)?;
+    save_position(deps.storage, &info.sender, lower_tick, upper_tick, &position)?;
+    POOL.save(deps.storage, &pool)?;
+
+    // whatever was not used goes back: native funds to the sender, cw20 tokens stay deposited
+    let mut response = Response::new();
+    for (i, asset_info) in config.asset_infos.iter().enumerate() {
+        let leftover = available[i].checked_sub(used[i])?;
+        match asset_info {
+            AssetInfo::NativeToken { .. } => {
+                if !leftover.is_zero() {
+                    let refund = Asset { info: asset_info.clone(), amount: leftover };
+                    response = response.add_message(refund.transfer_msg(&info.sender)?);
+                }
+            }
+            AssetInfo::Token { contract_addr } => {
+                DEPOSITS.save(deps.storage, (&info.sender, contract_addr.as_str()), &leftover)?;
+            }
+        }
+    }
+
+    Ok(response
+        .add_attribute("method", "provide_liquidity")
+        .add_attribute("owner", info.sender)
+        .add_attribute("lower_tick", lower_tick.to_string())
+        .add_attribute("upper_tick", upper_tick.to_string())
+        .add_attribute("liquidity", liquidity)
+        .add_attribute("amount_0", used[0])
+        .add_attribute("amount_1", used[1]))
+}
+
+pub fn withdraw_liquidity(
+    deps: DepsMut,
+    sender: Addr,
+    receiver: Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+    liquidity: Uint128,
+) -> Result<Response, ContractError> {
+    if liquidity.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let existing = load_position(deps.storage, &sender, lower_tick, upper_tick)?;
+    if existing.liquidity < liquidity {
+        return Err(ContractError::InsufficientPositionLiquidity { required: liquidity, available: existing.liquidity });
+    }
+
+    let mut pool = POOL.load(deps.storage)?;
+    let mut position = update_position(deps.storage, &mut pool, &sender, lower_tick, upper_tick, -signed_liquidity(liquidity)?)?;
+    let sqrt_price_lower = math::sqrt_price_at_tick(lower_tick)?;
+    let sqrt_price_upper = math::sqrt_price_at_tick(upper_tick)?;
+    let amounts = math::amounts_for_liquidity(pool.sqrt_price, sqrt_price_lower, sqrt_price_upper, liquidity, false)?;
+
+    let fees = position.tokens_owed;
+    position.tokens_owed = [Uint128::zero(); 2];
+    save_position(deps.storage, &sender, lower_tick, upper_tick, &position)?;
+    POOL.save(deps.storage, &pool)?;
+
+    let mut response = Response::new();
+    for (i, asset_info) in config.asset_infos.iter().enumerate() {
+        let payout = Asset { info: asset_info.clone(), amount: amounts[i].checked_add(fees[i])? };
+        if !payout.amount.is_zero() {
+            response = response.add_message(payout.transfer_msg(&receiver)?);
+        }
+    }
+
+    Ok(response
+        .add_attribute("method", "withdraw_liquidity")
+        .add_attribute("owner", sender)
+        .add_attribute("receiver", receiver)
+        .add_attribute("lower_tick", lower_tick.to_string())
+        .add_attribute("upper_tick", upper_tick.to_string())
+        .add_attribute("liquidity", liquidity)
+        .add_attribute("amount_0", amounts[0])
+        .add_attribute("amount_1", amounts[1])
+        .add_attribute("fees_0", fees[0])
+        .add_attribute("fees_1", fees[1]))
+}
+
+pub fn collect_fees(
+    deps: DepsMut,
+    sender: Addr,
+    receiver: Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let existing = load_position(deps.storage, &sender, lower_tick, upper_tick)?;
+
+    let mut pool = POOL.load(deps.storage)?;
+    let mut position = if existing.liquidity.is_zero() {
+        // a withdrawn position keeps only its owed fees, its ticks may be gone
+        existing
+    } else {
+        update_position(deps.storage, &mut pool, &sender, lower_tick, upper_tick, 0)?
+    };
+    let fees = position.tokens_owed;
+    position.tokens_owed = [Uint128::zero(); 2];
+    save_position(deps.storage, &sender, lower_tick, upper_tick, &position)?;
+
+    let mut response = Response::new();
+    for (i, asset_info) in config.asset_infos.iter().enumerate() {
+        let payout = Asset { info: asset_info.clone(), amount: fees[i] };
+        if !payout.amount.is_zero() {
+            response = response.add_message(payout.transfer_msg(&receiver)?);
+        }
+    }
+
+    Ok(response
+        .add_attribute("method", "collect_fees")
+        .add_attribute("owner", sender)
+        .add_attribute("receiver", receiver)
+        .add_attribute("lower_tick", lower_tick.to_string())
+        .add_attribute("upper_tick", upper_tick.to_string())
+        .add_attribute("fees_0", fees[0])
+        .add_attribute("fees_1", fees[1]))
+}
+
+fn load_position(
+    storage: &dyn Storage,
+    owner: &Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+) -> Result<Position, ContractError> {
+    POSITIONS
+        .may_load(storage, (owner, lower_tick, upper_tick))?
+        .ok_or(ContractError::PositionNotFound { owner: owner.to_string(), lower_tick, upper_tick })
+}
+
+pub struct SwapResult {
+    pub pool: PoolState,
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    // crossed ticks with their fee growth outside flipped, to be saved with the pool
+    pub crossed_ticks: Vec<(i32, TickInfo)>,
+}
+
+// Walks the initialized ticks in the swap direction, swapping within each range at its liquidity,
+// until amount is used up. Nothing is written, the caller saves the result.
+pub fn compute_swap(
+    storage: &dyn Storage,
+    config: &Config,
+    pool: &PoolState,
+    zero_for_one: bool,
+    amount: Uint128,
+) -> Result<SwapResult, ContractError> {
+    let mut pool = pool.clone();
+    let mut remaining = amount;
+    let mut return_amount = Uint128::zero();
+    let mut commission_amount = Uint128::zero();
+    let mut crossed_ticks = vec![];
+    let offer_index = if zero_for_one { 0 } else { 1 };
+
+    while !remaining.is_zero() {
+        // next initialized tick: at or below the current one going down, above it going up
+        let next = if zero_for_one {
+            TICKS.range(storage, None, Some(Bound::inclusive(pool.tick)), Order::Descending).next().transpose()?
+        } else {
+            TICKS.range(storage, Some(Bound::exclusive(pool.tick)), None, Order::Ascending).next().transpose()?
+        };
+        let target_tick = match &next {
+            Some((tick, _)) => *tick,
+            None if zero_for_one => MIN_TICK,
+            None => MAX_TICK,
+        };
+        let sqrt_price_target = math::sqrt_price_at_tick(target_tick)?;
+
+        let step = math::compute_swap_step(pool.sqrt_price, sqrt_price_target, pool.liquidity, remaining, config.fee_bps)?;
+        remaining = remaining.checked_sub(step.amount_in)?.checked_sub(step.fee_amount)?;
+        return_amount = return_amount.checked_add(step.amount_out)?;
+        commission_amount = commission_amount.checked_add(step.fee_amount)?;
+        if !pool.liquidity.is_zero() {
+            let growth = Decimal256::from_ratio(step.fee_amount, pool.liquidity);
+            pool.fee_growth_global[offer_index] = growth_add(pool.fee_growth_global[offer_index], growth);
+        }
+        pool.sqrt_price = step.sqrt_price_next;
+
+        if step.sqrt_price_next != sqrt_price_target {
+            pool.tick = math::tick_at_sqrt_price(pool.sqrt_price)?;
+            continue;
+        }
+        match next {
+            Some((tick, mut info)) => {
+                for i in 0..2 {
+                    info.fee_growth_outside[i] = growth_sub(pool.fee_growth_global[i], info.fee_growth_outside[i]);
+                }
+                let net = info.liquidity_net.i128();
+                if zero_for_one {
+                    pool.liquidity = apply_liquidity_delta(pool.liquidity, -net)?;
+                    pool.tick = tick - 1;
+                } else {
+                    pool.liquidity = apply_liquidity_delta(pool.liquidity, net)?;
+                    pool.tick = tick;
+                }
+                crossed_ticks.push((tick, info));
+            }
+            None => {
+                if !remaining.is_zero() {
+                    return Err(ContractError::InsufficientLiquidity { remaining });
+                }
+                pool.tick = target_tick;
+            }
+        }
+    }
+
+    Ok(SwapResult { pool, return_amount, commission_amount, crossed_ticks })
+}
+
+pub fn swap(
+    deps: DepsMut,
+    sender: Addr,
+    receiver: Addr,
+    offer_asset: Asset,
+    min_output: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    if offer_asset.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let offer_index = asset_index(&config, &offer_asset.info)?;
+    let ask_info = config.asset_infos[1 - offer_index].clone();
+
+    let pool = POOL.load(deps.storage)?;
+    let result = compute_swap(deps.storage, &config, &pool, offer_index == 0, offer_asset.amount)?;
+    if let Some(min_output) = min_output {
+        if result.return_amount < min_output {
+            return Err(ContractError::MinOutputNotReached { min_output, return_amount: result.return_amount });
+        }
+    }
+
+    for (tick, info) in result.crossed_ticks.iter() {
+        TICKS.save(deps.storage, *tick, info)?;
+    }
+    POOL.save(deps.storage, &result.pool)?;
+
+    let mut response = Response::new();
+    if !result.return_amount.is_zero() {
+        let payout = Asset { info: ask_info.clone(), amount: result.return_amount };
+        response = response.add_message(payout.transfer_msg(&receiver)?);
+    }
+    Ok(response
+        .add_attribute("action", "swap")
+        .add_attribute("sender", sender)
+        .add_attribute("receiver", receiver)
+        .add_attribute("offer_asset", offer_asset.info.to_string())
+        .add_attribute("ask_asset", ask_info.to_string())
+        .add_attribute("offer_amount", offer_asset.amount)
+        .add_attribute("return_amount", result.return_amount)
+        .add_attribute("commission_amount", result.commission_amount)
+        .add_attribute("ticks_crossed", result.crossed_ticks.len().to_string())
+        .add_attribute("tick", result.pool.tick.to_string()))
+}
+
+pub fn withdraw_deposits(
+    deps: DepsMut,
+    sender: Addr,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut response = Response::new()
+        .add_attribute("method", "withdraw_deposits")
+        .add_attribute("sender", sender.clone());
+    for asset_info in config.asset_infos.iter() {
+        if let AssetInfo::Token { contract_addr } = asset_info {
+            let key = (&sender, contract_addr.as_str());
+            let amount = DEPOSITS.may_load(deps.storage, key)?.unwrap_or_default();
+            DEPOSITS.remove(deps.storage, key);
+            let deposit = Asset { info: asset_info.clone(), amount };
+            if !amount.is_zero() {
+                response = response.add_message(deposit.transfer_msg(&sender)?);
+            }
+            response = response.add_attribute("withdrawn", deposit.to_string());
+        }
+    }
+    Ok(response)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(
+    deps: Deps,
+    _env: Env,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::Pool {} => to_json_binary(&query_pool(deps)?),
+        QueryMsg::Position { owner, lower_tick, upper_tick } => to_json_binary(&query_position(deps, owner, lower_tick, upper_tick)?),
+        QueryMsg::Positions { owner, start_after, limit } => to_json_binary(&query_positions(deps, owner, start_after, limit)?),
+        QueryMsg::Tick { tick } => to_json_binary(&query_tick(deps, tick)?),
+        QueryMsg::Simulation { offer_asset } => to_json_binary(&query_simulation(deps, offer_asset)?),
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        asset_infos: config.asset_infos,
+        tick_spacing: config.tick_spacing,
+        fee_bps: config.fee_bps,
+    })
+}
+
+pub fn query_pool(deps: Deps) -> StdResult<PoolResponse> {
+    let pool = POOL.load(deps.storage)?;
+    Ok(PoolResponse {
+        sqrt_price: pool.sqrt_price,
+        price: pool.sqrt_price.to_price().map_err(|err| StdError::generic_err(err.to_string()))?,
+        tick: pool.tick,
+        liquidity: pool.liquidity,
+        fee_growth_global: pool.fee_growth_global,
+    })
+}
+
+pub fn query_position(deps: Deps, owner: String, lower_tick: i32, upper_tick: i32) -> StdResult<PositionResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let pool = POOL.load(deps.storage)?;
+    let position = load_position(deps.storage, &owner, lower_tick, upper_tick).map_err(|err| StdError::generic_err(err.to_string()))?;
+    position_response(deps.storage, &pool, &owner, lower_tick, upper_tick, position).map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+pub fn query_positions(
+    deps: Deps,
+    owner: String,
+    start_after: Option<(i32, i32)>,
+    limit: Option<u32>,
+) -> StdResult<PositionsResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let pool = POOL.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let positions = POSITIONS
+        .sub_prefix(&owner)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let ((lower_tick, upper_tick), position) = item?;
+            position_response(deps.storage, &pool, &owner, lower_tick, upper_tick, position).map_err(|err| StdError::generic_err(err.to_string()))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(PositionsResponse { positions })
+}
+
+fn position_response(
+    storage: &dyn Storage,
+    pool: &PoolState,
+    owner: &Addr,
+    lower_tick: i32,
+    upper_tick: i32,
+    position: Position,
+) -> Result<PositionResponse, ContractError> {
+    let sqrt_price_lower = math::sqrt_price_at_tick(lower_tick)?;
+    let sqrt_price_upper = math::sqrt_price_at_tick(upper_tick)?;
+    let amounts = math::amounts_for_liquidity(pool.sqrt_price, sqrt_price_lower, sqrt_price_upper, position.liquidity, false)?;
+
+    let mut uncollected_fees = position.tokens_owed;
+    if !position.liquidity.is_zero() {
+        let lower = load_tick(storage, pool, lower_tick)?;
+        let upper = load_tick(storage, pool, upper_tick)?;
+        let inside = fee_growth_inside(pool, lower_tick, &lower, upper_tick, &upper);
+        let earned = fees_earned(&position, &inside)?;
+        uncollected_fees[0] = uncollected_fees[0].checked_add(earned[0])?;
+        uncollected_fees[1] = uncollected_fees[1].checked_add(earned[1])?;
+    }
+
+    Ok(PositionResponse {
+        owner: owner.to_string(),
+        lower_tick,
+        upper_tick,
+        liquidity: position.liquidity,
+        in_range: lower_tick <= pool.tick && pool.tick < upper_tick,
+        amounts,
+        uncollected_fees,
+    })
+}
+
+pub fn query_tick(deps: Deps, tick: i32) -> StdResult<TickResponse> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(StdError::generic_err(ContractError::InvalidTick { tick, min_tick: MIN_TICK, max_tick: MAX_TICK }.to_string()));
+    }
+    let sqrt_price = math::sqrt_price_at_tick(tick).map_err(|err| StdError::generic_err(err.to_string()))?;
+    let info = TICKS.may_load(deps.storage, tick)?.unwrap_or(TickInfo {
+        liquidity_gross: Uint128::zero(),
+        liquidity_net: Int128::zero(),
+        fee_growth_outside: [Decimal256::zero(); 2],
+    });
+    Ok(TickResponse {
+        tick,
+        sqrt_price,
+        liquidity_gross: info.liquidity_gross,
+        liquidity_net: info.liquidity_net,
+        fee_growth_outside: info.fee_growth_outside,
+    })
+}
+
+pub fn query_simulation(deps: Deps, offer_asset: Asset) -> StdResult<SimulationResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let pool = POOL.load(deps.storage)?;
+    let simulate = || -> Result<SimulationResponse, ContractError> {
+        let offer_index = asset_index(&config, &offer_asset.info)?;
+        let result = compute_swap(deps.storage, &config, &pool, offer_index == 0, offer_asset.amount)?;
+        Ok(SimulationResponse {
+            return_amount: result.return_amount,
+            commission_amount: result.commission_amount,
+            ticks_crossed: result.crossed_ticks.len() as u32,
+        })
+    };
+    simulate().map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, BankMsg, Coin, CosmosMsg, OwnedDeps};
+
+    const DENOM_0: &str = "uatom";
+    const DENOM_1: &str = "uosmo";
+
+    type TestDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    fn native(denom: &str) -> AssetInfo {
+        AssetInfo::NativeToken { denom: denom.to_string() }
+    }
+
+    // A native pool at price 1, tick 0, with a 0.3% fee
+    fn setup() -> TestDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            asset_infos: [native(DENOM_0), native(DENOM_1)],
+            tick_spacing: 10,
+            fee_bps: 30,
+            initial_price: Decimal256::one(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn provide(
+        deps: &mut TestDeps,
+        sender: &str,
+        ticks: (i32, i32),
+        amounts: (u128, u128),
+        min_liquidity: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ProvideLiquidity {
+            lower_tick: ticks.0,
+            upper_tick: ticks.1,
+            assets: [
+                Asset { info: native(DENOM_0), amount: Uint128::new(amounts.0) },
+                Asset { info: native(DENOM_1), amount: Uint128::new(amounts.1) },
+            ],
+            min_liquidity,
+        };
+        let funds: Vec<Coin> = [coin(amounts.0, DENOM_0), coin(amounts.1, DENOM_1)].into_iter().filter(|funds| !funds.amount.is_zero()).collect();
+        execute(deps.as_mut(), mock_env(), mock_info(sender, &funds), msg)
+    }
+
+    fn swap_native(deps: &mut TestDeps, denom: &str, amount: u128, min_output: Option<Uint128>) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(denom), amount: Uint128::new(amount) },
+            min_output,
+            to: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("trader", &[coin(amount, denom)]), msg)
+    }
+
+    fn attribute<'a>(res: &'a Response, key: &str) -> &'a str {
+        &res.attributes.iter().find(|attr| attr.key == key).unwrap_or_else(|| panic!("attribute {key} not found")).value
+    }
+
+    fn amount_attribute(res: &Response, key: &str) -> u128 {
+        attribute(res, key).parse().unwrap()
+    }
+
+    // Total of denom sent by the response, with its recipients
+    fn bank_sent(res: &Response, denom: &str) -> (u128, Vec<String>) {
+        let mut total = 0;
+        let mut recipients = vec![];
+        for sub_msg in res.messages.iter() {
+            if let CosmosMsg::Bank(BankMsg::Send { to_address, amount }) = &sub_msg.msg {
+                for funds in amount.iter().filter(|funds| funds.denom == denom) {
+                    total += funds.amount.u128();
+                    recipients.push(to_address.clone());
+                }
+            }
+        }
+        (total, recipients)
+    }
+
+    fn position(deps: &TestDeps, owner: &str, ticks: (i32, i32)) -> PositionResponse {
+        query_position(deps.as_ref(), owner.to_string(), ticks.0, ticks.1).unwrap()
+    }
+
+    #[test]
+    fn test_provide_liquidity() {
+        let mut deps = setup();
+
+        // the range is symmetric around the price, so both assets are used in equal parts
+        let res = provide(&mut deps, "alice", (-100, 100), (1_000_000, 1_200_000), None).unwrap();
+        let liquidity = Uint128::new(amount_attribute(&res, "liquidity"));
+        let used = (amount_attribute(&res, "amount_0"), amount_attribute(&res, "amount_1"));
+        assert!(used.0 <= 1_000_000 && used.0 + 1 >= 1_000_000, "{used:?}");
+        assert!(used.1.abs_diff(used.0) <= 1, "{used:?}");
+        assert_eq!(bank_sent(&res, DENOM_0).0, 1_000_000 - used.0);
+        assert_eq!(bank_sent(&res, DENOM_1), (1_200_000 - used.1, vec!["alice".to_string()]));
+
+        let pool = query_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.liquidity, liquidity);
+        assert_eq!(pool.tick, 0);
+        let lower = query_tick(deps.as_ref(), -100).unwrap();
+        let upper = query_tick(deps.as_ref(), 100).unwrap();
+        assert_eq!((lower.liquidity_gross, lower.liquidity_net), (liquidity, Int128::new(liquidity.u128() as i128)));
+        assert_eq!((upper.liquidity_gross, upper.liquidity_net), (liquidity, Int128::new(-(liquidity.u128() as i128))));
+
+        // above the price a range only holds asset 0 and leaves the pool liquidity alone
+        let res = provide(&mut deps, "bob", (200, 300), (500_000, 0), None).unwrap();
+        assert_eq!(amount_attribute(&res, "amount_1"), 0);
+        assert!(amount_attribute(&res, "amount_0") <= 500_000);
+        assert_eq!(query_pool(deps.as_ref()).unwrap().liquidity, liquidity);
+        let bob = position(&deps, "bob", (200, 300));
+        assert!(!bob.in_range);
+        assert_eq!(bob.amounts[1], Uint128::zero());
+
+        let err = provide(&mut deps, "alice", (-105, 100), (1_000, 1_000), None).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTickRange { lower_tick: -105, upper_tick: 100, .. }), "{err}");
+    }
+
+    #[test]
+    fn test_provide_liquidity_min_liquidity() {
+        let mut deps = setup();
+        let res = provide(&mut deps, "alice", (-100, 100), (1_000_000, 1_000_000), None).unwrap();
+        let liquidity = Uint128::new(amount_attribute(&res, "liquidity"));
+
+        // the same amounts back the same liquidity again
+        provide(&mut deps, "bob", (-100, 100), (1_000_000, 1_000_000), Some(liquidity)).unwrap();
+        let err = provide(&mut deps, "bob", (-100, 100), (1_000_000, 1_000_000), Some(liquidity + Uint128::one())).unwrap_err();
+        assert!(matches!(err, ContractError::MinLiquidityNotReached { min_liquidity, liquidity: provided } if min_liquidity == liquidity + Uint128::one() && provided == liquidity), "{err}");
+    }
+
+    #[test]
+    fn test_swap_crosses_initialized_tick() {
+        let mut deps = setup();
+        provide(&mut deps, "alice", (-100, 100), (1_000_000, 1_000_000), None).unwrap();
+        let res = provide(&mut deps, "bob", (-1_000, 1_000), (1_000_000, 1_000_000), None).unwrap();
+        let bob_liquidity = Uint128::new(amount_attribute(&res, "liquidity"));
+
+        // a swap within the narrow range crosses nothing
+        let res = swap_native(&mut deps, DENOM_0, 100_000, None).unwrap();
+        assert_eq!(attribute(&res, "ticks_crossed"), "0");
+        let tick: i32 = attribute(&res, "tick").parse().unwrap();
+        assert!((-100..0).contains(&tick), "{tick}");
+
+        // selling asset 0 moves the price down through -100, where alice's liquidity leaves
+        let simulation = query_simulation(deps.as_ref(), Asset { info: native(DENOM_0), amount: Uint128::new(1_500_000) }).unwrap();
+        assert_eq!(simulation.ticks_crossed, 1);
+        let res = swap_native(&mut deps, DENOM_0, 1_500_000, None).unwrap();
+        assert_eq!(attribute(&res, "ticks_crossed"), "1");
+        assert_eq!(amount_attribute(&res, "return_amount"), simulation.return_amount.u128());
+        assert_eq!(bank_sent(&res, DENOM_1), (simulation.return_amount.u128(), vec!["trader".to_string()]));
+
+        let pool = query_pool(deps.as_ref()).unwrap();
+        assert!(pool.tick < -100 && pool.tick >= -1_000, "{}", pool.tick);
+        assert_eq!(pool.liquidity, bob_liquidity);
+        assert!(!position(&deps, "alice", (-100, 100)).in_range);
+        assert!(position(&deps, "bob", (-1_000, 1_000)).in_range);
+        // the crossed tick now holds the fee growth above it
+        let crossed = query_tick(deps.as_ref(), -100).unwrap();
+        assert_ne!(crossed.fee_growth_outside[0], Decimal256::zero());
+    }
+
+    #[test]
+    fn test_swap_min_output() {
+        let mut deps = setup();
+        provide(&mut deps, "alice", (-100, 100), (1_000_000, 1_000_000), None).unwrap();
+        let simulation = query_simulation(deps.as_ref(), Asset { info: native(DENOM_1), amount: Uint128::new(10_000) }).unwrap();
+
+        let min_output = simulation.return_amount + Uint128::one();
+        let err = swap_native(&mut deps, DENOM_1, 10_000, Some(min_output)).unwrap_err();
+        assert!(matches!(err, ContractError::MinOutputNotReached { min_output: expected, return_amount } if expected == min_output && return_amount == simulation.return_amount), "{err}");
+
+        let res = swap_native(&mut deps, DENOM_1, 10_000, Some(simulation.return_amount)).unwrap();
+        assert_eq!(bank_sent(&res, DENOM_0).0, simulation.return_amount.u128());
+    }
+
+    #[test]
+    fn test_fees_accrue_to_positions_in_range() {
+        let mut deps = setup();
+        provide(&mut deps, "alice", (-100, 100), (1_000_000, 1_000_000), None).unwrap();
+        provide(&mut deps, "bob", (-100, 100), (3_000_000, 3_000_000), None).unwrap();
+        provide(&mut deps, "carol", (200, 300), (1_000_000, 0), None).unwrap();
+
+        let res = swap_native(&mut deps, DENOM_0, 200_000, None).unwrap();
+        let commission = amount_attribute(&res, "commission_amount");
+        assert_eq!(commission, 600);
+
+        // alice holds a quarter of the liquidity in range, bob three quarters, carol is out of range
+        let alice = position(&deps, "alice", (-100, 100));
+        let bob = position(&deps, "bob", (-100, 100));
+        let carol = position(&deps, "carol", (200, 300));
+        assert!(alice.in_range && bob.in_range && !carol.in_range);
+        assert_eq!(alice.uncollected_fees[1], Uint128::zero());
+        assert!(alice.uncollected_fees[0].u128().abs_diff(commission / 4) <= 1, "{:?}", alice.uncollected_fees);
+        assert!(bob.uncollected_fees[0].u128().abs_diff(commission * 3 / 4) <= 1, "{:?}", bob.uncollected_fees);
+        assert!(alice.uncollected_fees[0] + bob.uncollected_fees[0] <= Uint128::new(commission));
+        assert_eq!(carol.uncollected_fees, [Uint128::zero(); 2]);
+
+        // swapping back accrues asset 1 fees the same way
+        swap_native(&mut deps, DENOM_1, 200_000, None).unwrap();
+        let alice = position(&deps, "alice", (-100, 100));
+        assert!(alice.uncollected_fees[1].u128().abs_diff(150) <= 1, "{:?}", alice.uncollected_fees);
+        assert_eq!(position(&deps, "carol", (200, 300)).uncollected_fees, [Uint128::zero(); 2]);
+    }
+
+    #[test]
+    fn test_collect_and_withdraw_conserve_reserves() {
+        let mut deps = setup();
+        // native balance of the pool, per asset
+        let mut reserves = [0u128; 2];
+        for (owner, ticks, amounts) in [("alice", (-100, 100), (1_000_000, 1_000_000)), ("bob", (-1_000, 500), (2_000_000, 1_500_000)), ("carol", (200, 300), (700_000, 0))] {
+            let res = provide(&mut deps, owner, ticks, amounts, None).unwrap();
+            reserves[0] += amount_attribute(&res, "amount_0");
+            reserves[1] += amount_attribute(&res, "amount_1");
+        }
+        for (denom, amount) in [(DENOM_0, 1_200_000), (DENOM_1, 2_500_000), (DENOM_0, 300_000)] {
+            let res = swap_native(&mut deps, denom, amount, None).unwrap();
+            let (offer, ask) = if denom == DENOM_0 { (0, 1) } else { (1, 0) };
+            reserves[offer] += amount;
+            reserves[ask] -= amount_attribute(&res, "return_amount");
+        }
+
+        // collecting pays exactly the fees the query reports and resets them
+        let owed = position(&deps, "alice", (-100, 100)).uncollected_fees;
+        assert!(!owed[0].is_zero() && !owed[1].is_zero());
+        let msg = ExecuteMsg::CollectFees { lower_tick: -100, upper_tick: 100, to: Some("alice_wallet".to_string()) };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        assert_eq!(bank_sent(&res, DENOM_0), (owed[0].u128(), vec!["alice_wallet".to_string()]));
+        assert_eq!(bank_sent(&res, DENOM_1).0, owed[1].u128());
+        assert_eq!(position(&deps, "alice", (-100, 100)).uncollected_fees, [Uint128::zero(); 2]);
+        reserves[0] -= owed[0].u128();
+        reserves[1] -= owed[1].u128();
+
+        // withdrawing pays the amounts and the fees the query reports
+        for (owner, ticks) in [("alice", (-100, 100)), ("bob", (-1_000, 500)), ("carol", (200, 300))] {
+            let before = position(&deps, owner, ticks);
+            let msg = ExecuteMsg::WithdrawLiquidity { lower_tick: ticks.0, upper_tick: ticks.1, liquidity: before.liquidity, to: None };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), msg).unwrap();
+            for i in 0..2 {
+                let paid = bank_sent(&res, [DENOM_0, DENOM_1][i]).0;
+                assert_eq!(paid, (before.amounts[i] + before.uncollected_fees[i]).u128(), "{owner} asset {i}");
+                reserves[i] = reserves[i].checked_sub(paid).unwrap_or_else(|| panic!("{owner} is paid more than the pool holds"));
+            }
+            let err = query_position(deps.as_ref(), owner.to_string(), ticks.0, ticks.1).unwrap_err();
+            assert!(err.to_string().contains("PositionNotFound"), "{err}");
+        }
+
+        // only rounding dust is left behind, and the pool is empty
+        assert!(reserves[0] <= 10 && reserves[1] <= 10, "{reserves:?}");
+        assert_eq!(query_pool(deps.as_ref()).unwrap().liquidity, Uint128::zero());
+        assert!(TICKS.is_empty(&deps.storage));
+    }
+}
@@ -0,0 +1,75 @@
+use cosmwasm_std::{CheckedFromRatioError, ConversionOverflowError, DivideByZeroError, OverflowError, StdError, Uint128};
+use cw_utils::PaymentError;
+use dex::asset::UnexpectedFunds;
+use thiserror::Error;
+
+use crate::math::SqrtPrice;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
+    #[error("{0}")]
+    ConversionOverflow(#[from] ConversionOverflowError),
+
+    #[error("{0}")]
+    CheckedFromRatio(#[from] CheckedFromRatioError),
+
+    #[error("ZeroAmount: amount must be above zero")]
+    ZeroAmount {},
+
+    #[error("InvalidTokenPair: pool assets must be different")]
+    InvalidTokenPair {},
+
+    #[error("UnsupportedToken: {asset} is not an asset of this pool")]
+    UnsupportedToken { asset: String },
+
+    #[error("{0}")]
+    UnexpectedFunds(#[from] UnexpectedFunds),
+
+    #[error("InvalidFee: fee {fee_bps} bps is above {max_fee_bps} bps")]
+    InvalidFee { fee_bps: u16, max_fee_bps: u16 },
+
+    #[error("InvalidTickSpacing: tick spacing must be above zero")]
+    InvalidTickSpacing {},
+
+    #[error("InvalidTickRange: ticks {lower_tick} and {upper_tick} must be ordered, multiples of the tick spacing and between {min_tick} and {max_tick}")]
+    InvalidTickRange { lower_tick: i32, upper_tick: i32, min_tick: i32, max_tick: i32 },
+
+    #[error("InvalidPrice: sqrt price {sqrt_price} is outside of the range of ticks {min_tick} to {max_tick}")]
+    InvalidPrice { sqrt_price: SqrtPrice, min_tick: i32, max_tick: i32 },
+
+    #[error("InvalidTick: tick {tick} must be between {min_tick} and {max_tick}")]
+    InvalidTick { tick: i32, min_tick: i32, max_tick: i32 },
+
+    #[error("InsufficientFunds: expected {expected}{asset}, received {received}")]
+    InsufficientFunds { asset: String, expected: Uint128, received: Uint128 },
+
+    #[error("InsufficientLiquidity: the swap moves the price out of the tick range before {remaining} is used up")]
+    InsufficientLiquidity { remaining: Uint128 },
+
+    #[error("InsufficientPositionLiquidity: position holds {available} liquidity, {required} requested")]
+    InsufficientPositionLiquidity { required: Uint128, available: Uint128 },
+
+    #[error("PositionNotFound: no position of {owner} between ticks {lower_tick} and {upper_tick}")]
+    PositionNotFound { owner: String, lower_tick: i32, upper_tick: i32 },
+
+    #[error("Cw20OfferNotReceived: {asset} must be offered through the cw20 Receive hook")]
+    Cw20OfferNotReceived { asset: String },
+
+    #[error("MinOutputNotReached: returned {return_amount}, expected at least {min_output}")]
+    MinOutputNotReached { min_output: Uint128, return_amount: Uint128 },
+
+    #[error("MinLiquidityNotReached: provided {liquidity} liquidity, expected at least {min_liquidity}")]
+    MinLiquidityNotReached { min_liquidity: Uint128, liquidity: Uint128 },
+}
@@ -0,0 +1,404 @@
+// Concentrated liquidity arithmetic, after Uniswap V3. Prices are square roots of the price of
+// asset 0 in asset 1, as SqrtPrice; a tick t stands for the price 1.0001^t. Within a tick range
+// the reserves follow x * y = L^2 with
+//   amount 0 between sqrt prices a < b: L * (b - a) / (a * b)
+//   amount 1 between sqrt prices a < b: L * (b - a)
+// Intermediate products are taken on Uint512 over the SqrtPrice atomics. Amounts paid out round
+// down, amounts taken in round up.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal256, Isqrt, Uint128, Uint256, Uint512};
+use std::fmt;
+
+use crate::error::ContractError;
+
+// Uniswap's bounds, sqrt prices of about 2^-64 and 2^64: prices from 2.9e-39 to 3.4e38
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+const SQRT_PRICE_ONE: u128 = 1_000_000_000_000_000_000_000_000_000_000_000_000;
+
+// sqrt(1.0001 ^ -(2 ^ i)) with 36 decimals, the factors of the sqrt price of a tick by its bits
+const SQRT_TICK_FACTORS: [u128; 20] = [
+    999_950_003_749_687_527_341_289_288_064_992_197,
+    999_900_009_999_000_099_990_000_999_900_009_999,
+    999_800_029_996_000_499_940_006_999_200_089_990,
+    999_600_099_980_003_499_440_083_988_001_649_780,
+    999_200_359_880_032_992_081_715_656_864_338_561,
+    998_401_359_184_387_445_014_246_950_501_832_822,
+    996_805_274_021_232_232_403_522_589_364_153_187,
+    993_620_754_316_543_878_477_700_834_205_485_601,
+    987_282_203_408_577_650_514_394_977_141_072_622,
+    974_726_149_167_296_094_959_767_608_987_808_722,
+    950_091_065_870_505_957_793_733_166_276_036_749,
+    902_673_033_446_954_090_815_966_611_100_428_427,
+    814_818_605_312_325_898_736_980_010_634_637_721,
+    663_929_359_563_123_931_326_022_095_378_263_637,
+    440_802_194_489_899_902_842_800_301_640_729_327,
+    194_306_574_667_111_540_266_988_334_939_575_749,
+    37_755_044_958_865_792_175_320_904_477_540_520,
+    1_425_443_419_845_977_266_771_800_329_344_378,
+    2_031_888_943_182_195_016_802_617_844_386,
+    4_128_572_677_426_057_329_255_071,
+];
+
+// Square root of a price, fixed point with 36 decimals. Decimal256 and its 18 decimals would
+// leave nothing of the sqrt prices at the low end of the tick range.
+#[cw_serde]
+#[derive(Copy, Eq, PartialOrd, Ord)]
+pub struct SqrtPrice(Uint256);
+
+impl SqrtPrice {
+    pub const fn new(atomics: Uint256) -> Self {
+        Self(atomics)
+    }
+
+    pub const fn one() -> Self {
+        Self(Uint256::from_u128(SQRT_PRICE_ONE))
+    }
+
+    pub fn atomics(&self) -> Uint256 {
+        self.0
+    }
+
+    // Square root of `price`, rounded down
+    pub fn from_price(price: Decimal256) -> Result<Self, ContractError> {
+        // price atomics have 18 decimals, 54 more make the 72 of the square of a sqrt price
+        let scaled = Uint512::from(price.atomics()).checked_mul(Uint512::from(10u8).checked_pow(54)?)?;
+        Ok(Self(Uint256::try_from(scaled.isqrt())?))
+    }
+
+    // The price, rounded down to the 18 decimals of Decimal256
+    pub fn to_price(&self) -> Result<Decimal256, ContractError> {
+        let atomics = atomics(*self);
+        let squared = atomics.checked_mul(atomics)?.checked_div(Uint512::from(10u8).checked_pow(54)?)?;
+        Ok(Decimal256::new(Uint256::try_from(squared)?))
+    }
+}
+
+impl fmt::Display for SqrtPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let one = Uint256::from(SQRT_PRICE_ONE);
+        let (whole, fractional) = (self.0 / one, self.0 % one);
+        if fractional.is_zero() {
+            write!(f, "{}", whole)
+        } else {
+            let fractional = format!("{:0>36}", fractional.to_string());
+            write!(f, "{}.{}", whole, fractional.trim_end_matches('0'))
+        }
+    }
+}
+
+fn one_atomics() -> Uint512 {
+    Uint512::from(SQRT_PRICE_ONE)
+}
+
+fn atomics(value: SqrtPrice) -> Uint512 {
+    Uint512::from(value.atomics())
+}
+
+// b - a over the atomics, b at least a
+fn difference(a: SqrtPrice, b: SqrtPrice) -> Result<Uint512, ContractError> {
+    Ok(atomics(b).checked_sub(atomics(a))?)
+}
+
+fn mul_div(a: Uint512, b: Uint512, c: Uint512, round_up: bool) -> Result<Uint512, ContractError> {
+    let numerator = a.checked_mul(b)?;
+    let mut result = numerator.checked_div(c)?;
+    if round_up && !numerator.checked_rem(c)?.is_zero() {
+        result = result.checked_add(Uint512::one())?;
+    }
+    Ok(result)
+}
+
+fn to_uint128(value: Uint512) -> Result<Uint128, ContractError> {
+    Ok(Uint128::try_from(Uint256::try_from(value)?)?)
+}
+
+fn to_sqrt_price(atomics: Uint512) -> Result<SqrtPrice, ContractError> {
+    Ok(SqrtPrice::new(Uint256::try_from(atomics)?))
+}
+
+// sqrt(1.0001 ^ tick), rounded down: the product of the factors of the bits of |tick|, kept with
+// 72 decimals, inverted for positive ticks
+pub fn sqrt_price_at_tick(tick: i32) -> Result<SqrtPrice, ContractError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(ContractError::InvalidTick { tick, min_tick: MIN_TICK, max_tick: MAX_TICK });
+    }
+    let abs_tick = tick.unsigned_abs();
+    let one = one_atomics();
+    let mut ratio = one.checked_mul(one)?;
+    for (bit, factor) in SQRT_TICK_FACTORS.iter().enumerate() {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = ratio.checked_mul(Uint512::from(*factor))?.checked_div(one)?;
+        }
+    }
+    if tick > 0 {
+        to_sqrt_price(one.checked_mul(one)?.checked_mul(one)?.checked_div(ratio)?)
+    } else {
+        to_sqrt_price(ratio.checked_div(one)?)
+    }
+}
+
+// Largest tick whose sqrt price is at most `sqrt_price`
+pub fn tick_at_sqrt_price(sqrt_price: SqrtPrice) -> Result<i32, ContractError> {
+    if sqrt_price < sqrt_price_at_tick(MIN_TICK)? || sqrt_price > sqrt_price_at_tick(MAX_TICK)? {
+        return Err(ContractError::InvalidPrice { sqrt_price, min_tick: MIN_TICK, max_tick: MAX_TICK });
+    }
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+// Amount of asset 0 between two sqrt prices for `liquidity`
+pub fn amount_0_delta(
+    sqrt_price_a: SqrtPrice,
+    sqrt_price_b: SqrtPrice,
+    liquidity: Uint128,
+    round_up: bool,
+) -> Result<Uint128, ContractError> {
+    let (lower, upper) = if sqrt_price_a < sqrt_price_b { (sqrt_price_a, sqrt_price_b) } else { (sqrt_price_b, sqrt_price_a) };
+    let numerator = Uint512::from(liquidity).checked_mul(difference(lower, upper)?)?;
+    let denominator = atomics(lower).checked_mul(atomics(upper))?;
+    to_uint128(mul_div(numerator, one_atomics(), denominator, round_up)?)
+}
+
+// Amount of asset 1 between two sqrt prices for `liquidity`
+pub fn amount_1_delta(
+    sqrt_price_a: SqrtPrice,
+    sqrt_price_b: SqrtPrice,
+    liquidity: Uint128,
+    round_up: bool,
+) -> Result<Uint128, ContractError> {
+    let (lower, upper) = if sqrt_price_a < sqrt_price_b { (sqrt_price_a, sqrt_price_b) } else { (sqrt_price_b, sqrt_price_a) };
+    to_uint128(mul_div(Uint512::from(liquidity), difference(lower, upper)?, one_atomics(), round_up)?)
+}
+
+// Amounts of both assets backing `liquidity` between the sqrt prices of a range, at `sqrt_price`
+pub fn amounts_for_liquidity(
+    sqrt_price: SqrtPrice,
+    sqrt_price_lower: SqrtPrice,
+    sqrt_price_upper: SqrtPrice,
+    liquidity: Uint128,
+    round_up: bool,
+) -> Result<[Uint128; 2], ContractError> {
+    if sqrt_price <= sqrt_price_lower {
+        Ok([amount_0_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?, Uint128::zero()])
+    } else if sqrt_price < sqrt_price_upper {
+        Ok([
+            amount_0_delta(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+            amount_1_delta(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+        ])
+    } else {
+        Ok([Uint128::zero(), amount_1_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?])
+    }
+}
+
+// Largest liquidity that the given amounts can back over the range, at `sqrt_price`
+pub fn liquidity_for_amounts(
+    sqrt_price: SqrtPrice,
+    sqrt_price_lower: SqrtPrice,
+    sqrt_price_upper: SqrtPrice,
+    amounts: &[Uint128; 2],
+) -> Result<Uint128, ContractError> {
+    // amount_0 * a * b / (b - a) and amount_1 / (b - a), the inverses of the deltas above
+    let liquidity_0 = |lower: SqrtPrice, upper: SqrtPrice| -> Result<Uint128, ContractError> {
+        let numerator = Uint512::from(amounts[0]).checked_mul(atomics(lower))?;
+        let denominator = difference(lower, upper)?.checked_mul(one_atomics())?;
+        to_uint128(mul_div(numerator, atomics(upper), denominator, false)?)
+    };
+    let liquidity_1 = |lower: SqrtPrice, upper: SqrtPrice| -> Result<Uint128, ContractError> {
+        to_uint128(mul_div(Uint512::from(amounts[1]), one_atomics(), difference(lower, upper)?, false)?)
+    };
+
+    if sqrt_price <= sqrt_price_lower {
+        liquidity_0(sqrt_price_lower, sqrt_price_upper)
+    } else if sqrt_price < sqrt_price_upper {
+        Ok(liquidity_0(sqrt_price, sqrt_price_upper)?.min(liquidity_1(sqrt_price_lower, sqrt_price)?))
+    } else {
+        liquidity_1(sqrt_price_lower, sqrt_price_upper)
+    }
+}
+
+// Sqrt price once `amount_in` is swapped in at `liquidity`: L * s / (L + amount * s) for asset 0,
+// moving the price down, rounded up; s + amount / L for asset 1, moving it up, rounded down
+pub fn next_sqrt_price_from_input(
+    sqrt_price: SqrtPrice,
+    liquidity: Uint128,
+    amount_in: Uint128,
+    zero_for_one: bool,
+) -> Result<SqrtPrice, ContractError> {
+    let liquidity = Uint512::from(liquidity);
+    if zero_for_one {
+        let numerator = liquidity.checked_mul(atomics(sqrt_price))?;
+        let denominator = liquidity
+            .checked_mul(one_atomics())?
+            .checked_add(Uint512::from(amount_in).checked_mul(atomics(sqrt_price))?)?;
+        to_sqrt_price(mul_div(numerator, one_atomics(), denominator, true)?)
+    } else {
+        let delta = mul_div(Uint512::from(amount_in), one_atomics(), liquidity, false)?;
+        to_sqrt_price(atomics(sqrt_price).checked_add(delta)?)
+    }
+}
+
+pub struct SwapStep {
+    pub sqrt_price_next: SqrtPrice,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    pub fee_amount: Uint128,
+}
+
+// Swaps as much of `amount_remaining` (fee included) as the range up to `sqrt_price_target` takes
+pub fn compute_swap_step(
+    sqrt_price: SqrtPrice,
+    sqrt_price_target: SqrtPrice,
+    liquidity: Uint128,
+    amount_remaining: Uint128,
+    fee_bps: u16,
+) -> Result<SwapStep, ContractError> {
+    let zero_for_one = sqrt_price_target < sqrt_price;
+    if liquidity.is_zero() {
+        return Ok(SwapStep {
+            sqrt_price_next: sqrt_price_target,
+            amount_in: Uint128::zero(),
+            amount_out: Uint128::zero(),
+            fee_amount: Uint128::zero(),
+        });
+    }
+
+    let fee_complement = Uint128::from(BPS_DENOMINATOR - fee_bps as u128);
+    let denominator = Uint128::from(BPS_DENOMINATOR);
+    let amount_remaining_net = amount_remaining.multiply_ratio(fee_complement, denominator);
+
+    let max_in = if zero_for_one {
+        amount_0_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+    } else {
+        amount_1_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+    };
+
+    let (sqrt_price_next, amount_in, fee_amount) = if amount_remaining_net >= max_in {
+        let fee = to_uint128(mul_div(Uint512::from(max_in), Uint512::from(fee_bps), Uint512::from(fee_complement), true)?)?;
+        (sqrt_price_target, max_in, fee)
+    } else {
+        let next = next_sqrt_price_from_input(sqrt_price, liquidity, amount_remaining_net, zero_for_one)?;
+        (next, amount_remaining_net, amount_remaining - amount_remaining_net)
+    };
+
+    let amount_out = if zero_for_one {
+        amount_1_delta(sqrt_price_next, sqrt_price, liquidity, false)?
+    } else {
+        amount_0_delta(sqrt_price, sqrt_price_next, liquidity, false)?
+    };
+
+    Ok(SwapStep { sqrt_price_next, amount_in, amount_out, fee_amount })
+}
+
+// amount * growth, rounded down, for the fees earned by `amount` of liquidity
+pub fn mul_growth(amount: Uint128, growth: Decimal256) -> Result<Uint128, ContractError> {
+    let one = Uint512::from(Decimal256::one().atomics());
+    to_uint128(mul_div(Uint512::from(amount), Uint512::from(growth.atomics()), one, false)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn sqrt_price(atomics: &str) -> SqrtPrice {
+        SqrtPrice::new(Uint256::from_str(atomics).unwrap())
+    }
+
+    // Each factor is truncated to 36 decimals, so the error grows with the number of bits set
+    fn assert_close(actual: SqrtPrice, expected: SqrtPrice) {
+        let diff = actual.atomics().abs_diff(expected.atomics());
+        let tolerance = expected.atomics() / Uint256::from(10u128.pow(34)) + Uint256::from(10u8);
+        assert!(diff <= tolerance, "{actual} is not within 1e-34 of {expected}");
+    }
+
+    #[test]
+    fn sqrt_price_at_tick_matches_reference() {
+        // 1.0001 ^ (t / 2) with 36 decimals, from Python's decimal module at 120 digits
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), SqrtPrice::one());
+        assert_close(sqrt_price_at_tick(2).unwrap(), sqrt_price("1000100000000000000000000000000000000"));
+        assert_close(sqrt_price_at_tick(-2).unwrap(), sqrt_price("999900009999000099990000999900009999"));
+        assert_close(sqrt_price_at_tick(46_054).unwrap(), sqrt_price("9999997796810696239179435305647163829"));
+        assert_close(sqrt_price_at_tick(MIN_TICK).unwrap(), sqrt_price("54212146310449513"));
+        // 2^64 above one, the last factors only keep 25 significant digits
+        let max = sqrt_price_at_tick(MAX_TICK).unwrap().atomics();
+        let expected = Uint256::from_str("18446050711097703529776342895396472065568967222426633323").unwrap();
+        assert!(max.abs_diff(expected) < expected / Uint256::from(10u128.pow(24)), "{max}");
+    }
+
+    #[test]
+    fn tick_at_sqrt_price_rounds_down() {
+        for tick in [MIN_TICK, -400_000, -46_054, -1, 0, 1, 12_345, 400_000, MAX_TICK] {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+            if tick < MAX_TICK {
+                let between = SqrtPrice::new(sqrt_price.atomics() + Uint256::one());
+                assert_eq!(tick_at_sqrt_price(between).unwrap(), tick);
+            }
+        }
+    }
+
+    #[test]
+    fn prices_are_bounded_by_the_tick_range() {
+        let below = SqrtPrice::new(sqrt_price_at_tick(MIN_TICK).unwrap().atomics() - Uint256::one());
+        let above = SqrtPrice::new(sqrt_price_at_tick(MAX_TICK).unwrap().atomics() + Uint256::one());
+        for sqrt_price in [below, above] {
+            let err = tick_at_sqrt_price(sqrt_price).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidPrice { .. }), "{err}");
+        }
+        assert!(matches!(sqrt_price_at_tick(MAX_TICK + 1).unwrap_err(), ContractError::InvalidTick { .. }));
+    }
+
+    #[test]
+    fn sqrt_price_converts_from_and_to_price() {
+        // pairs of tokens with 6 and 18 decimals trade around 1e-12 and 1e12
+        let low = SqrtPrice::from_price(Decimal256::from_str("0.000000000001").unwrap()).unwrap();
+        assert_eq!(low, sqrt_price("1000000000000000000000000000000"));
+        assert_eq!(low.to_string(), "0.000001");
+        let high = SqrtPrice::from_price(Decimal256::from_str("1000000000000").unwrap()).unwrap();
+        assert_eq!(high.to_price().unwrap(), Decimal256::from_str("1000000000000").unwrap());
+        assert_eq!(tick_at_sqrt_price(low).unwrap(), -276_325);
+        assert_eq!(tick_at_sqrt_price(high).unwrap(), 276_324);
+    }
+
+    #[test]
+    fn liquidity_for_amounts_never_needs_more() {
+        // 1e9 liquidity between ticks -1000 and 1000 at price 1 holds 48_768_197.58 of each asset
+        let (lower, upper) = (sqrt_price_at_tick(-1_000).unwrap(), sqrt_price_at_tick(1_000).unwrap());
+        let amounts = [Uint128::new(48_768_197), Uint128::new(48_768_198)];
+        let liquidity = liquidity_for_amounts(SqrtPrice::one(), lower, upper, &amounts).unwrap();
+        assert!(liquidity < Uint128::new(1_000_000_000) && liquidity > Uint128::new(999_999_980), "{liquidity}");
+        let needed = amounts_for_liquidity(SqrtPrice::one(), lower, upper, liquidity, true).unwrap();
+        assert!(needed[0] <= amounts[0] && needed[1] <= amounts[1], "{needed:?}");
+    }
+
+    #[test]
+    fn swap_step_within_range_matches_constant_product() {
+        // L = 1_000_000 at price 1 is x = y = 1_000_000: 1_000 of asset 0 returns 1_000_000 * 1_000 / 1_001_000
+        let target = sqrt_price_at_tick(-10_000).unwrap();
+        let step = compute_swap_step(SqrtPrice::one(), target, Uint128::new(1_000_000), Uint128::new(1_000), 0).unwrap();
+        assert_eq!(step.amount_in, Uint128::new(1_000));
+        assert_eq!(step.amount_out, Uint128::new(999));
+        assert!(step.sqrt_price_next > target);
+    }
+
+    #[test]
+    fn swap_step_stops_at_target() {
+        let target = sqrt_price_at_tick(10).unwrap();
+        let step = compute_swap_step(SqrtPrice::one(), target, Uint128::new(1_000_000), Uint128::new(1_000_000), 30).unwrap();
+        assert_eq!(step.sqrt_price_next, target);
+        assert_eq!(step.amount_in, amount_1_delta(SqrtPrice::one(), target, Uint128::new(1_000_000), true).unwrap());
+        assert!(step.amount_in + step.fee_amount <= Uint128::new(1_000_000));
+    }
+}
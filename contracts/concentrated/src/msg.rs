@@ -0,0 +1,149 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Decimal256, Int128, Uint128};
+use cw20::Cw20ReceiveMsg;
+use dex::asset::{Asset, AssetInfo};
+
+use crate::math::SqrtPrice;
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub asset_infos: [AssetInfo; 2],
+    pub tick_spacing: i32,
+    pub fee_bps: u16,
+    // price of asset 0 in asset 1 to open the pool at, above zero: the ticks cover 2.9e-39 to
+    // 3.4e38, below 1e-18 the price only moves through swaps
+    pub initial_price: Decimal256,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    Receive(Cw20ReceiveMsg),
+    // adds liquidity to the sender's position between the two ticks, taking at most the given
+    // amounts from the native funds sent and the sender's cw20 deposits, the rest is refunded
+    ProvideLiquidity {
+        lower_tick: i32,
+        upper_tick: i32,
+        assets: [Asset; 2],
+        // reverts when the liquidity the assets back is below this
+        min_liquidity: Option<Uint128>,
+    },
+    // removes liquidity from the sender's position and pays it out with the fees owed
+    WithdrawLiquidity {
+        lower_tick: i32,
+        upper_tick: i32,
+        liquidity: Uint128,
+        // receives the assets instead of the sender
+        to: Option<String>,
+    },
+    // pays out the fees owed to the sender's position
+    CollectFees {
+        lower_tick: i32,
+        upper_tick: i32,
+        to: Option<String>,
+    },
+    // native offer only, cw20 offers go through Receive
+    Swap {
+        offer_asset: Asset,
+        // reverts when the return amount is below this
+        min_output: Option<Uint128>,
+        to: Option<String>,
+    },
+    // returns the cw20 deposits not used by ProvideLiquidity
+    WithdrawDeposits {},
+}
+
+// Messages embedded in a cw20 Send to the pool
+#[cw_serde]
+pub enum Cw20HookMsg {
+    Swap {
+        min_output: Option<Uint128>,
+        to: Option<String>,
+    },
+    // credits the tokens for a later ProvideLiquidity
+    Deposit {},
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(ConfigResponse)]
+    Config {},
+
+    #[returns(PoolResponse)]
+    Pool {},
+
+    #[returns(PositionResponse)]
+    Position {
+        owner: String,
+        lower_tick: i32,
+        upper_tick: i32,
+    },
+
+    // positions of owner, ordered by (lower_tick, upper_tick)
+    #[returns(PositionsResponse)]
+    Positions {
+        owner: String,
+        start_after: Option<(i32, i32)>,
+        limit: Option<u32>,
+    },
+
+    #[returns(TickResponse)]
+    Tick { tick: i32 },
+
+    // return amount of a swap of offer_asset, at the current price and liquidity
+    #[returns(SimulationResponse)]
+    Simulation { offer_asset: Asset },
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub asset_infos: [AssetInfo; 2],
+    pub tick_spacing: i32,
+    pub fee_bps: u16,
+}
+
+#[cw_serde]
+pub struct PoolResponse {
+    pub sqrt_price: SqrtPrice,
+    // rounded down to 18 decimals
+    pub price: Decimal256,
+    pub tick: i32,
+    pub liquidity: Uint128,
+    pub fee_growth_global: [Decimal256; 2],
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    pub owner: String,
+    pub lower_tick: i32,
+    pub upper_tick: i32,
+    pub liquidity: Uint128,
+    // whether the current price is inside the range, so that the position earns fees
+    pub in_range: bool,
+    // assets the liquidity would withdraw at the current price
+    pub amounts: [Uint128; 2],
+    // fees owed to the position, including those earned since its last update
+    pub uncollected_fees: [Uint128; 2],
+}
+
+#[cw_serde]
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+}
+
+#[cw_serde]
+pub struct TickResponse {
+    pub tick: i32,
+    pub sqrt_price: SqrtPrice,
+    pub liquidity_gross: Uint128,
+    pub liquidity_net: Int128,
+    pub fee_growth_outside: [Decimal256; 2],
+}
+
+#[cw_serde]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    // ticks the swap crosses
+    pub ticks_crossed: u32,
+}
@@ -0,0 +1,51 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal256, Int128, Uint128};
+use cw_storage_plus::{Item, Map};
+use dex::asset::AssetInfo;
+
+use crate::math::SqrtPrice;
+
+#[cw_serde]
+pub struct Config {
+    pub asset_infos: [AssetInfo; 2],
+    pub tick_spacing: i32, // Position bounds must be multiples of it
+    pub fee_bps: u16,      // Swap fee, paid to the positions in range
+}
+
+#[cw_serde]
+pub struct PoolState {
+    pub sqrt_price: SqrtPrice,              // Square root of the price of asset 0 in asset 1
+    pub tick: i32,                          // Tick of sqrt_price, rounded down
+    pub liquidity: Uint128,                 // Liquidity of the positions whose range contains tick
+    pub fee_growth_global: [Decimal256; 2], // Fees earned per unit of liquidity since instantiation
+}
+
+#[cw_serde]
+pub struct TickInfo {
+    pub liquidity_gross: Uint128, // Liquidity of all positions bounded by this tick
+    pub liquidity_net: Int128,    // Liquidity added when the price crosses this tick upwards, removed downwards
+    // Fee growth on the other side of this tick from the current price, only differences between
+    // the values of two ticks are meaningful
+    pub fee_growth_outside: [Decimal256; 2],
+}
+
+#[cw_serde]
+pub struct Position {
+    pub liquidity: Uint128,
+    pub fee_growth_inside_last: [Decimal256; 2], // Fee growth inside the range at the last update
+    pub tokens_owed: [Uint128; 2],               // Fees credited at the last update and not collected yet
+}
+
+//storage for the pool config
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const POOL: Item<PoolState> = Item::new("pool");
+
+// Initialized ticks, those bounding at least one position
+pub const TICKS: Map<i32, TickInfo> = Map::new("ticks");
+
+// Positions keyed by (owner, lower tick, upper tick)
+pub const POSITIONS: Map<(&Addr, i32, i32), Position> = Map::new("positions");
+
+// cw20 tokens sent through the Deposit hook and not yet used, keyed by (user, token contract)
+pub const DEPOSITS: Map<(&Addr, &str), Uint128> = Map::new("deposits");
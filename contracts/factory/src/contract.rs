@@ -0,0 +1,349 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Order, Reply, Response, StdResult, SubMsg, WasmMsg};
+use cw_storage_plus::Bound;
+use cw_utils::parse_reply_instantiate_data;
+use dex::asset::AssetInfo;
+use dex::state::PairType;
+use dex::msg::{ContractInfoResponse as PairContractInfoResponse, InstantiateMsg as PairInstantiateMsg, QueryMsg as PairQueryMsg};
+
+use crate::error::ContractError;
+use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, PairsResponse, QueryMsg};
+use crate::state::{pair_key, Config, PairInfo, CONFIG, PAIRS, TMP_PAIR_INFO};
+
+const INSTANTIATE_PAIR_REPLY_ID: u64 = 1;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let config = Config {
+        owner: info.sender.clone(),
+        pair_code_id: msg.pair_code_id,
+        token_code_id: msg.token_code_id,
+    };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("owner", info.sender)
+        .add_attribute("pair_code_id", msg.pair_code_id.to_string())
+        .add_attribute("token_code_id", msg.token_code_id.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::CreatePair { asset_infos, pair_type, amp } => create_pair(deps, env, info, asset_infos, pair_type, amp),
+    }
+}
+
+pub fn create_pair(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    asset_infos: [AssetInfo; 2],
+    pair_type: Option<PairType>,
+    amp: Option<u64>,
+) -> Result<Response, ContractError> {
+    // cw20 addresses must be valid and normalized, the pair key is built from the raw strings
+    asset_infos[0].check(deps.api)?;
+    asset_infos[1].check(deps.api)?;
+    if asset_infos[0] == asset_infos[1] {
+        return Err(ContractError::IdenticalAssets {});
+    }
+
+    let key = pair_key(&asset_infos);
+    if PAIRS.has(deps.storage, &key) {
+        return Err(ContractError::PairExists {
+            asset_0: asset_infos[0].to_string(),
+            asset_1: asset_infos[1].to_string(),
+        });
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let pair_type = pair_type.unwrap_or(PairType::Xyk {});
+
+    // contract_addr and liquidity_token are filled in by the reply
+    TMP_PAIR_INFO.save(deps.storage, &PairInfo {
+        asset_infos: asset_infos.clone(),
+        contract_addr: String::new(),
+        liquidity_token: String::new(),
+        pair_type: pair_type.clone(),
+    })?;
+
+    let instantiate_msg = WasmMsg::Instantiate {
+        admin: Some(config.owner.to_string()),
+        code_id: config.pair_code_id,
+        msg: to_json_binary(&PairInstantiateMsg {
+            owner: config.owner.to_string(),
+            asset_infos: asset_infos.clone(),
+            token_code_id: config.token_code_id,
+            lp_fee_bps: None,
+            protocol_fee_bps: None,
+            fee_collector: None,
+            pair_type: Some(pair_type),
+            amp,
+        })?,
+        funds: vec![],
+        label: format!("dex pair {}-{}", asset_infos[0], asset_infos[1]),
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate_msg, INSTANTIATE_PAIR_REPLY_ID))
+        .add_attribute("action", "create_pair")
+        .add_attribute("factory", env.contract.address)
+        .add_attribute("pair", format!("{}-{}", asset_infos[0], asset_infos[1])))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        INSTANTIATE_PAIR_REPLY_ID => register_pair(deps, msg),
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+pub fn register_pair(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let res = parse_reply_instantiate_data(msg)?;
+    let pair_addr = deps.api.addr_validate(&res.contract_address)?;
+
+    let mut pair_info = TMP_PAIR_INFO.load(deps.storage)?;
+    TMP_PAIR_INFO.remove(deps.storage);
+    pair_info.contract_addr = pair_addr.to_string();
+
+    // the pair has registered its LP token in its own reply by now
+    let pair_contract_info: PairContractInfoResponse = deps.querier.query_wasm_smart(&pair_addr, &PairQueryMsg::QueryContractInfo {})?;
+    pair_info.liquidity_token = pair_contract_info.lpt_contract;
+
+    PAIRS.save(deps.storage, &pair_key(&pair_info.asset_infos), &pair_info)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_pair")
+        .add_attribute("pair_contract_addr", pair_addr))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(
+    deps: Deps,
+    _env: Env,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::Pair { asset_infos } => to_json_binary(&query_pair(deps, asset_infos)?),
+        QueryMsg::Pairs { start_after, limit } => to_json_binary(&query_pairs(deps, start_after, limit)?),
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: config.owner.to_string(),
+        pair_code_id: config.pair_code_id,
+        token_code_id: config.token_code_id,
+    })
+}
+
+pub fn query_pair(deps: Deps, asset_infos: [AssetInfo; 2]) -> StdResult<PairInfo> {
+    asset_infos[0].check(deps.api)?;
+    asset_infos[1].check(deps.api)?;
+    PAIRS.load(deps.storage, &pair_key(&asset_infos))
+}
+
+pub fn query_pairs(
+    deps: Deps,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+) -> StdResult<PairsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(asset_infos) => {
+            asset_infos[0].check(deps.api)?;
+            asset_infos[1].check(deps.api)?;
+            Some(Bound::ExclusiveRaw(pair_key(&asset_infos)))
+        }
+        None => None,
+    };
+
+    let pairs = PAIRS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, pair_info)| pair_info))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PairsResponse { pairs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{from_json, ContractResult, CosmosMsg, OwnedDeps, ReplyOn, SubMsgResponse, SubMsgResult, SystemResult, WasmQuery};
+
+    fn native(denom: &str) -> AssetInfo {
+        AssetInfo::NativeToken { denom: denom.to_string() }
+    }
+
+    fn token(contract_addr: &str) -> AssetInfo {
+        AssetInfo::Token { contract_addr: contract_addr.to_string() }
+    }
+
+    // Factory whose pairs answer QueryContractInfo with the LP token "<pair>_lpt"
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } => SystemResult::Ok(ContractResult::Ok(
+                to_json_binary(&PairContractInfoResponse {
+                    owner: "owner".to_string(),
+                    asset_infos: [native("uatom"), native("uosmo")],
+                    lpt_contract: format!("{contract_addr}_lpt"),
+                    lp_fee_bps: 30,
+                    protocol_fee_bps: 0,
+                    fee_collector: None,
+                    pair_type: PairType::Xyk {},
+                    guardian: None,
+                })
+                .unwrap(),
+            )),
+            _ => unimplemented!(),
+        });
+        let msg = InstantiateMsg { pair_code_id: 2, token_code_id: 3 };
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        deps
+    }
+
+    fn create(deps: DepsMut, asset_infos: [AssetInfo; 2]) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::CreatePair { asset_infos, pair_type: None, amp: None };
+        execute(deps, mock_env(), mock_info("alice", &[]), msg)
+    }
+
+    // Reply to the pair instantiation, data is the protobuf MsgInstantiateContractResponse
+    fn instantiate_reply(id: u64, contract_addr: &str) -> Reply {
+        let mut data = vec![0x0a, contract_addr.len() as u8];
+        data.extend_from_slice(contract_addr.as_bytes());
+        Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: Some(Binary::from(data)) }),
+        }
+    }
+
+    fn create_and_register(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, asset_infos: [AssetInfo; 2], pair: &str) {
+        create(deps.as_mut(), asset_infos).unwrap();
+        reply(deps.as_mut(), mock_env(), instantiate_reply(INSTANTIATE_PAIR_REPLY_ID, pair)).unwrap();
+    }
+
+    #[test]
+    fn test_create_pair_instantiates_the_pair() {
+        let mut deps = setup();
+        let res = create(deps.as_mut(), [native("uatom"), token("token")]).unwrap();
+
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, INSTANTIATE_PAIR_REPLY_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+        let CosmosMsg::Wasm(WasmMsg::Instantiate { admin, code_id, msg, .. }) = &res.messages[0].msg else {
+            panic!("unexpected message {:?}", res.messages[0].msg);
+        };
+        assert_eq!(admin.as_deref(), Some("owner"));
+        assert_eq!(*code_id, 2);
+        let msg: PairInstantiateMsg = from_json(msg).unwrap();
+        assert_eq!(msg.owner, "owner");
+        assert_eq!(msg.asset_infos, [native("uatom"), token("token")]);
+        assert_eq!(msg.token_code_id, 3);
+        assert_eq!(msg.pair_type, Some(PairType::Xyk {}));
+
+        // the pair is only registered once its address is known
+        let tmp = TMP_PAIR_INFO.load(&deps.storage).unwrap();
+        assert_eq!(tmp.asset_infos, [native("uatom"), token("token")]);
+        assert!(query_pair(deps.as_ref(), [native("uatom"), token("token")]).is_err());
+    }
+
+    #[test]
+    fn test_reply_registers_the_pair() {
+        let mut deps = setup();
+        create(deps.as_mut(), [native("uatom"), token("token")]).unwrap();
+        let res = reply(deps.as_mut(), mock_env(), instantiate_reply(INSTANTIATE_PAIR_REPLY_ID, "pair")).unwrap();
+        assert!(res.attributes.iter().any(|attr| attr.key == "pair_contract_addr" && attr.value == "pair"));
+
+        assert!(TMP_PAIR_INFO.may_load(&deps.storage).unwrap().is_none());
+        let expected = PairInfo {
+            asset_infos: [native("uatom"), token("token")],
+            contract_addr: "pair".to_string(),
+            liquidity_token: "pair_lpt".to_string(),
+            pair_type: PairType::Xyk {},
+        };
+        assert_eq!(query_pair(deps.as_ref(), [native("uatom"), token("token")]).unwrap(), expected);
+        // either order finds it
+        assert_eq!(query_pair(deps.as_ref(), [token("token"), native("uatom")]).unwrap(), expected);
+
+        let err = reply(deps.as_mut(), mock_env(), instantiate_reply(7, "pair")).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownReplyId { id: 7 }), "{err}");
+    }
+
+    #[test]
+    fn test_create_pair_rejects_duplicates_and_identical_assets() {
+        let mut deps = setup();
+        create_and_register(&mut deps, [native("uatom"), token("token")], "pair");
+
+        let err = create(deps.as_mut(), [native("uatom"), token("token")]).unwrap_err();
+        assert!(matches!(err, ContractError::PairExists { .. }), "{err}");
+        let err = create(deps.as_mut(), [token("token"), native("uatom")]).unwrap_err();
+        assert!(matches!(err, ContractError::PairExists { .. }), "{err}");
+
+        let err = create(deps.as_mut(), [native("uosmo"), native("uosmo")]).unwrap_err();
+        assert!(matches!(err, ContractError::IdenticalAssets {}), "{err}");
+    }
+
+    #[test]
+    fn test_cw20_addresses_are_validated() {
+        let mut deps = setup();
+        create_and_register(&mut deps, [native("uatom"), token("token")], "pair");
+
+        // "TOKEN" would otherwise get a key of its own and a second pair of the same assets
+        let err = create(deps.as_mut(), [native("uatom"), token("TOKEN")]).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)), "{err}");
+        let err = create(deps.as_mut(), [token("TOKEN"), token("TOKEN")]).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)), "{err}");
+
+        let err = query_pair(deps.as_ref(), [native("uatom"), token("TOKEN")]).unwrap_err();
+        assert!(err.to_string().contains("Invalid input"), "{err}");
+        let err = query_pairs(deps.as_ref(), Some([native("uatom"), token("TOKEN")]), None).unwrap_err();
+        assert!(err.to_string().contains("Invalid input"), "{err}");
+    }
+
+    #[test]
+    fn test_pairs_pagination() {
+        let mut deps = setup();
+        create_and_register(&mut deps, [native("uatom"), native("uosmo")], "pair_ao");
+        create_and_register(&mut deps, [native("uosmo"), token("token")], "pair_ot");
+        create_and_register(&mut deps, [native("uatom"), token("token")], "pair_at");
+
+        let all = query_pairs(deps.as_ref(), None, None).unwrap().pairs;
+        assert_eq!(all.len(), 3);
+        // ordered by key, not by creation
+        let keys: Vec<_> = all.iter().map(|pair| pair_key(&pair.asset_infos)).collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+
+        let first = query_pairs(deps.as_ref(), None, Some(2)).unwrap().pairs;
+        assert_eq!(first, all[..2]);
+        let [a, b] = first[1].asset_infos.clone();
+        let rest = query_pairs(deps.as_ref(), Some([a.clone(), b.clone()]), Some(2)).unwrap().pairs;
+        assert_eq!(rest, all[2..]);
+        // start_after in the other order pages the same
+        assert_eq!(query_pairs(deps.as_ref(), Some([b, a]), None).unwrap().pairs, all[2..]);
+
+        let last = all[2].asset_infos.clone();
+        assert!(query_pairs(deps.as_ref(), Some(last), None).unwrap().pairs.is_empty());
+    }
+}
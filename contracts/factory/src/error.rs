@@ -0,0 +1,21 @@
+use cosmwasm_std::StdError;
+use cw_utils::ParseReplyError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("Pair assets must be different")]
+    IdenticalAssets {},
+
+    #[error("Pair {asset_0}-{asset_1} already exists")]
+    PairExists { asset_0: String, asset_1: String },
+
+    #[error("Unknown reply id {id}")]
+    UnknownReplyId { id: u64 },
+}
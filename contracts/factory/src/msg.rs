@@ -0,0 +1,47 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use dex::asset::AssetInfo;
+use dex::state::PairType;
+
+use crate::state::PairInfo;
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub pair_code_id: u64,
+    pub token_code_id: u64,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    CreatePair {
+        asset_infos: [AssetInfo; 2],
+        // constant product when not set
+        pair_type: Option<PairType>,
+        // initial amplification, required for stable pairs
+        amp: Option<u64>,
+    },
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(ConfigResponse)]
+    Config {},
+
+    #[returns(PairInfo)]
+    Pair { asset_infos: [AssetInfo; 2] },
+
+    #[returns(PairsResponse)]
+    Pairs { start_after: Option<[AssetInfo; 2]>, limit: Option<u32> },
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub owner: String,
+    pub pair_code_id: u64,
+    pub token_code_id: u64,
+}
+
+#[cw_serde]
+pub struct PairsResponse {
+    pub pairs: Vec<PairInfo>,
+}
@@ -0,0 +1,71 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Addr;
+use cw_storage_plus::{Item, Map};
+use dex::asset::AssetInfo;
+use dex::state::PairType;
+
+#[cw_serde]
+pub struct Config {
+    pub owner: Addr,
+    pub pair_code_id: u64,
+    pub token_code_id: u64,
+}
+
+#[cw_serde]
+pub struct PairInfo {
+    pub asset_infos: [AssetInfo; 2],
+    pub contract_addr: String,
+    pub liquidity_token: String,
+    pub pair_type: PairType,
+}
+
+//storage for the factory config
+pub const CONFIG: Item<Config> = Item::new("config");
+
+// Registry of created pairs, keyed by `pair_key` of their asset infos
+pub const PAIRS: Map<&[u8], PairInfo> = Map::new("pairs");
+
+// Pair being instantiated, read back in the reply once its address is known
+pub const TMP_PAIR_INFO: Item<PairInfo> = Item::new("tmp_pair_info");
+
+// Order independent key of a pair: the two encoded asset infos sorted and concatenated. Each one
+// is tagged with its kind and length-prefixed, so that no two pairs share a key, be it ("ab", "c")
+// and ("a", "bc") or a denom and a contract address spelled the same.
+pub fn pair_key(asset_infos: &[AssetInfo; 2]) -> Vec<u8> {
+    let mut keys = [asset_key(&asset_infos[0]), asset_key(&asset_infos[1])];
+    keys.sort();
+    keys.concat()
+}
+
+fn asset_key(asset_info: &AssetInfo) -> Vec<u8> {
+    let (tag, id) = match asset_info {
+        AssetInfo::NativeToken { denom } => (0u8, denom.as_bytes()),
+        AssetInfo::Token { contract_addr } => (1u8, contract_addr.as_bytes()),
+    };
+    [&[tag][..], &(id.len() as u32).to_be_bytes(), id].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn native(denom: &str) -> AssetInfo {
+        AssetInfo::NativeToken { denom: denom.to_string() }
+    }
+
+    fn token(contract_addr: &str) -> AssetInfo {
+        AssetInfo::Token { contract_addr: contract_addr.to_string() }
+    }
+
+    #[test]
+    fn pair_key_is_order_independent() {
+        assert_eq!(pair_key(&[native("a"), token("b")]), pair_key(&[token("b"), native("a")]));
+    }
+
+    #[test]
+    fn pair_keys_do_not_collide() {
+        assert_ne!(pair_key(&[native("ab"), native("c")]), pair_key(&[native("a"), native("bc")]));
+        assert_ne!(pair_key(&[native("x"), native("y")]), pair_key(&[native("x"), token("y")]));
+        assert_ne!(pair_key(&[native("x"), token("y")]), pair_key(&[token("x"), native("y")]));
+    }
+}
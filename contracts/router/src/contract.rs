@@ -0,0 +1,598 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{from_json, to_json_binary, Addr, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, QuerierWrapper, Response, StdError, StdResult, Uint128, WasmMsg};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw_utils::must_pay;
+use dex::asset::{recipient, Asset, AssetInfo};
+use dex::msg::{Cw20HookMsg as PairCw20HookMsg, ExecuteMsg as PairExecuteMsg, QueryMsg as PairQueryMsg, SimulationResponse};
+use dex_factory::msg::QueryMsg as FactoryQueryMsg;
+use dex_factory::state::PairInfo;
+
+use crate::error::ContractError;
+use crate::msg::{ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg, SimulateSwapOperationsResponse, SwapOperation};
+use crate::state::{Config, CONFIG};
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let dex_factory = deps.api.addr_validate(&msg.dex_factory)?;
+    CONFIG.save(deps.storage, &Config { dex_factory: dex_factory.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("dex_factory", dex_factory))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Receive(cw20_msg) => receive_cw20(deps, env, info, cw20_msg),
+        ExecuteMsg::ExecuteSwapOperations { operations, minimum_receive, to } => {
+            if let Some(first) = operations.first() {
+                match &first.offer_asset_info {
+                    // the first hop swaps the router's whole balance, which must be what was just sent
+                    AssetInfo::NativeToken { denom } => {
+                        must_pay(&info, denom)?;
+                    }
+                    AssetInfo::Token { .. } => {
+                        return Err(ContractError::Cw20OfferNotReceived { asset: first.offer_asset_info.to_string() });
+                    }
+                }
+            }
+            execute_swap_operations(deps, env, info.sender, operations, minimum_receive, to)
+        }
+        ExecuteMsg::ExecuteSwapOperation { operation, to } => execute_swap_operation(deps, env, info, operation, to),
+        ExecuteMsg::AssertMinimumReceive { asset_info, prev_balance, minimum_receive, receiver } => {
+            assert_minimum_receive(deps.as_ref(), asset_info, prev_balance, minimum_receive, receiver)
+        }
+    }
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+
+    match from_json(&cw20_msg.msg)? {
+        Cw20HookMsg::ExecuteSwapOperations { operations, minimum_receive, to } => {
+            // info.sender is the cw20 contract that forwarded the tokens, it must be the first offer
+            let received = AssetInfo::Token { contract_addr: info.sender.to_string() };
+            if let Some(first) = operations.first() {
+                if first.offer_asset_info != received {
+                    return Err(ContractError::Unauthorized {});
+                }
+            }
+            execute_swap_operations(deps, env, sender, operations, minimum_receive, to)
+        }
+    }
+}
+
+fn assert_operations(operations: &[SwapOperation]) -> Result<(), ContractError> {
+    if operations.is_empty() {
+        return Err(ContractError::MustProvideOperations {});
+    }
+    for pair in operations.windows(2) {
+        if pair[0].ask_asset_info != pair[1].offer_asset_info {
+            return Err(ContractError::InvalidOperations { ask_asset: pair[0].ask_asset_info.to_string() });
+        }
+    }
+    Ok(())
+}
+
+// Runs every hop as a callback on the router itself, each one swapping the whole router balance
+// of its offer asset, so the output of a hop is the input of the next one. The last hop pays out
+// to the receiver, whose balance is then checked against minimum_receive.
+pub fn execute_swap_operations(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    operations: Vec<SwapOperation>,
+    minimum_receive: Option<Uint128>,
+    to: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_operations(&operations)?;
+
+    let receiver = recipient(deps.api, to, &sender)?;
+    let target_asset_info = operations[operations.len() - 1].ask_asset_info.clone();
+
+    let last_index = operations.len() - 1;
+    let mut messages = operations
+        .into_iter()
+        .enumerate()
+        .map(|(i, operation)| {
+            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_json_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation,
+                    to: if i == last_index { Some(receiver.to_string()) } else { None },
+                })?,
+                funds: vec![],
+            }))
+        })
+        .collect::<StdResult<Vec<CosmosMsg>>>()?;
+
+    if let Some(minimum_receive) = minimum_receive {
+        let prev_balance = target_asset_info.query_balance(&deps.querier, &receiver)?;
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_json_binary(&ExecuteMsg::AssertMinimumReceive {
+                asset_info: target_asset_info.clone(),
+                prev_balance,
+                minimum_receive,
+                receiver: receiver.to_string(),
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("action", "execute_swap_operations")
+        .add_attribute("receiver", receiver)
+        .add_attribute("ask_asset", target_asset_info.to_string()))
+}
+
+pub fn execute_swap_operation(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operation: SwapOperation,
+    to: Option<String>,
+) -> Result<Response, ContractError> {
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let pair_info = query_pair_info(&deps.querier, &config.dex_factory, &operation)?;
+
+    let amount = operation.offer_asset_info.query_balance(&deps.querier, &env.contract.address)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToSwap { asset: operation.offer_asset_info.to_string() });
+    }
+
+    let swap_msg = match &operation.offer_asset_info {
+        AssetInfo::NativeToken { denom } => WasmMsg::Execute {
+            contract_addr: pair_info.contract_addr.clone(),
+            msg: to_json_binary(&PairExecuteMsg::Swap {
+                offer_asset: Asset { info: operation.offer_asset_info.clone(), amount },
+                min_output: None,
+                belief_price: None,
+                max_spread: None,
+                deadline: None,
+                to,
+            })?,
+            funds: vec![Coin { denom: denom.to_string(), amount }],
+        },
+        AssetInfo::Token { contract_addr } => WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: pair_info.contract_addr.clone(),
+                amount,
+                msg: to_json_binary(&PairCw20HookMsg::Swap {
+                    min_output: None,
+                    belief_price: None,
+                    max_spread: None,
+                    deadline: None,
+                    to,
+                })?,
+            })?,
+            funds: vec![],
+        },
+    };
+
+    Ok(Response::new()
+        .add_message(swap_msg)
+        .add_attribute("action", "execute_swap_operation")
+        .add_attribute("pair", pair_info.contract_addr)
+        .add_attribute("offer_asset", operation.offer_asset_info.to_string())
+        .add_attribute("offer_amount", amount.to_string())
+        .add_attribute("ask_asset", operation.ask_asset_info.to_string()))
+}
+
+pub fn assert_minimum_receive(
+    deps: Deps,
+    asset_info: AssetInfo,
+    prev_balance: Uint128,
+    minimum_receive: Uint128,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let receiver = deps.api.addr_validate(&receiver)?;
+    let balance = asset_info.query_balance(&deps.querier, &receiver)?;
+    let amount = balance.saturating_sub(prev_balance);
+    if amount < minimum_receive {
+        return Err(ContractError::AssertionMinimumReceive { minimum_receive, amount });
+    }
+    Ok(Response::new()
+        .add_attribute("action", "assert_minimum_receive")
+        .add_attribute("amount", amount.to_string()))
+}
+
+fn query_pair_info(
+    querier: &QuerierWrapper,
+    dex_factory: &Addr,
+    operation: &SwapOperation,
+) -> StdResult<PairInfo> {
+    querier.query_wasm_smart(dex_factory, &FactoryQueryMsg::Pair {
+        asset_infos: [operation.offer_asset_info.clone(), operation.ask_asset_info.clone()],
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(
+    deps: Deps,
+    _env: Env,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::SimulateSwapOperations { offer_amount, operations } => {
+            to_json_binary(&simulate_swap_operations(deps, offer_amount, operations)?)
+        }
+    }
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse { dex_factory: config.dex_factory.to_string() })
+}
+
+// Chains the pairs' Simulation queries, the output of each hop is offered to the next one
+pub fn simulate_swap_operations(
+    deps: Deps,
+    offer_amount: Uint128,
+    operations: Vec<SwapOperation>,
+) -> StdResult<SimulateSwapOperationsResponse> {
+    assert_operations(&operations).map_err(|err| StdError::generic_err(err.to_string()))?;
+    let config = CONFIG.load(deps.storage)?;
+
+    let mut amount = offer_amount;
+    for operation in operations.iter() {
+        let pair_info = query_pair_info(&deps.querier, &config.dex_factory, operation)?;
+        let simulation: SimulationResponse = deps.querier.query_wasm_smart(&pair_info.contract_addr, &PairQueryMsg::Simulation {
+            offer_asset: Asset { info: operation.offer_asset_info.clone(), amount },
+        })?;
+        amount = simulation.return_amount;
+    }
+
+    Ok(SimulateSwapOperationsResponse { amount })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, ContractResult, OwnedDeps, SystemError, SystemResult, WasmQuery};
+    use cw20::{BalanceResponse, Cw20QueryMsg};
+    use cw_utils::PaymentError;
+    use dex::state::PairType;
+
+    const DENOM_A: &str = "uatom";
+    const DENOM_B: &str = "uosmo";
+    const TOKEN_C: &str = "token";
+    // cw20 balance of the router, the only holder the mock token knows about
+    const ROUTER_TOKEN_BALANCE: u128 = 500;
+
+    type TestDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    fn native(denom: &str) -> AssetInfo {
+        AssetInfo::NativeToken { denom: denom.to_string() }
+    }
+
+    fn token(contract_addr: &str) -> AssetInfo {
+        AssetInfo::Token { contract_addr: contract_addr.to_string() }
+    }
+
+    fn operation(offer: AssetInfo, ask: AssetInfo) -> SwapOperation {
+        SwapOperation { offer_asset_info: offer, ask_asset_info: ask }
+    }
+
+    // uatom -> uosmo -> token
+    fn operations() -> Vec<SwapOperation> {
+        vec![operation(native(DENOM_A), native(DENOM_B)), operation(native(DENOM_B), token(TOKEN_C))]
+    }
+
+    fn pair_addr(asset_infos: &[AssetInfo; 2]) -> Option<&'static str> {
+        let has = |info: AssetInfo| asset_infos.contains(&info);
+        if has(native(DENOM_A)) && has(native(DENOM_B)) {
+            Some("pair_ab")
+        } else if has(native(DENOM_B)) && has(token(TOKEN_C)) {
+            Some("pair_bc")
+        } else {
+            None
+        }
+    }
+
+    // The factory knows the uatom/uosmo and uosmo/token pairs, both of which return 90% of the offer
+    fn handle_wasm_query(query: &WasmQuery) -> cosmwasm_std::QuerierResult {
+        let WasmQuery::Smart { contract_addr, msg } = query else {
+            return SystemResult::Err(SystemError::UnsupportedRequest { kind: "wasm".to_string() });
+        };
+        let response = match contract_addr.as_str() {
+            "factory" => match from_json(msg).unwrap() {
+                FactoryQueryMsg::Pair { asset_infos } => match pair_addr(&asset_infos) {
+                    Some(pair) => to_json_binary(&PairInfo {
+                        asset_infos,
+                        contract_addr: pair.to_string(),
+                        liquidity_token: format!("{pair}_lpt"),
+                        pair_type: PairType::Xyk {},
+                    }),
+                    None => Err(StdError::not_found("pair info")),
+                },
+                _ => panic!("unexpected factory query"),
+            },
+            "pair_ab" | "pair_bc" => match from_json(msg).unwrap() {
+                PairQueryMsg::Simulation { offer_asset } => to_json_binary(&SimulationResponse {
+                    return_amount: offer_asset.amount.multiply_ratio(9u128, 10u128),
+                    spread_amount: Uint128::zero(),
+                    commission_amount: offer_asset.amount.multiply_ratio(1u128, 10u128),
+                }),
+                _ => panic!("unexpected pair query"),
+            },
+            TOKEN_C => match from_json(msg).unwrap() {
+                Cw20QueryMsg::Balance { address } => {
+                    let balance = if address == mock_env().contract.address { ROUTER_TOKEN_BALANCE } else { 0 };
+                    to_json_binary(&BalanceResponse { balance: Uint128::new(balance) })
+                }
+                _ => panic!("unexpected token query"),
+            },
+            _ => return SystemResult::Err(SystemError::NoSuchContract { addr: contract_addr.clone() }),
+        };
+        SystemResult::Ok(ContractResult::from(response))
+    }
+
+    fn setup() -> TestDeps {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(handle_wasm_query);
+        let msg = InstantiateMsg { dex_factory: "factory".to_string() };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        deps
+    }
+
+    fn attribute<'a>(res: &'a Response, key: &str) -> &'a str {
+        &res.attributes.iter().find(|attr| attr.key == key).unwrap_or_else(|| panic!("attribute {key} not found")).value
+    }
+
+    // The messages the router sends to itself, with their funds
+    fn callbacks(res: &Response) -> Vec<ExecuteMsg> {
+        res.messages
+            .iter()
+            .map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds }) => {
+                    assert_eq!(contract_addr, mock_env().contract.address.as_str());
+                    assert!(funds.is_empty());
+                    from_json(msg).unwrap()
+                }
+                msg => panic!("unexpected message {msg:?}"),
+            })
+            .collect()
+    }
+
+    fn execute_operations(deps: &mut TestDeps, funds: &[Coin], minimum_receive: Option<Uint128>, to: Option<&str>) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ExecuteSwapOperations {
+            operations: operations(),
+            minimum_receive,
+            to: to.map(str::to_string),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", funds), msg)
+    }
+
+    // Runs a callback as the router itself, the way the chain delivers it
+    fn execute_callback(deps: &mut TestDeps, msg: ExecuteMsg) -> Result<Response, ContractError> {
+        let router = mock_env().contract.address;
+        execute(deps.as_mut(), mock_env(), mock_info(router.as_str(), &[]), msg)
+    }
+
+    #[test]
+    fn test_execute_swap_operations_chains_hops() {
+        let mut deps = setup();
+        let res = execute_operations(&mut deps, &[coin(1_000, DENOM_A)], None, None).unwrap();
+        assert_eq!(attribute(&res, "receiver"), "alice");
+        assert_eq!(attribute(&res, "ask_asset"), TOKEN_C);
+
+        // one callback per hop, only the last one pays out to the receiver
+        let hops = callbacks(&res);
+        assert_eq!(
+            hops,
+            vec![
+                ExecuteMsg::ExecuteSwapOperation { operation: operations()[0].clone(), to: None },
+                ExecuteMsg::ExecuteSwapOperation { operation: operations()[1].clone(), to: Some("alice".to_string()) },
+            ]
+        );
+
+        // each hop swaps the router's whole balance of its offer asset, not the amount sent
+        let router = mock_env().contract.address;
+        deps.querier.update_balance(router.as_str(), vec![coin(1_000, DENOM_A)]);
+        let res = execute_callback(&mut deps, hops[0].clone()).unwrap();
+        assert_eq!(attribute(&res, "pair"), "pair_ab");
+        assert_eq!(attribute(&res, "offer_amount"), "1000");
+        let expected = PairExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "pair_ab".to_string(), msg: to_json_binary(&expected).unwrap(), funds: vec![coin(1_000, DENOM_A)] })
+        );
+
+        // the first hop returned 900 uosmo, plus 5 left over from before
+        deps.querier.update_balance(router.as_str(), vec![coin(905, DENOM_B)]);
+        let res = execute_callback(&mut deps, hops[1].clone()).unwrap();
+        assert_eq!(attribute(&res, "pair"), "pair_bc");
+        assert_eq!(attribute(&res, "offer_amount"), "905");
+        let CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds }) = &res.messages[0].msg else {
+            panic!("expected a pair swap");
+        };
+        assert_eq!((contract_addr.as_str(), funds.clone()), ("pair_bc", vec![coin(905, DENOM_B)]));
+        let PairExecuteMsg::Swap { offer_asset, to, .. } = from_json(msg).unwrap() else {
+            panic!("expected a pair swap");
+        };
+        assert_eq!((offer_asset.amount, to), (Uint128::new(905), Some("alice".to_string())));
+    }
+
+    #[test]
+    fn test_execute_swap_operation_cw20_offer() {
+        let mut deps = setup();
+        let operation = operation(token(TOKEN_C), native(DENOM_B));
+        let res = execute_callback(&mut deps, ExecuteMsg::ExecuteSwapOperation { operation, to: Some("bob".to_string()) }).unwrap();
+
+        // a cw20 hop sends the router's token balance to the pair with the Swap hook
+        let expected = Cw20ExecuteMsg::Send {
+            contract: "pair_bc".to_string(),
+            amount: Uint128::new(ROUTER_TOKEN_BALANCE),
+            msg: to_json_binary(&PairCw20HookMsg::Swap {
+                min_output: None,
+                belief_price: None,
+                max_spread: None,
+                deadline: None,
+                to: Some("bob".to_string()),
+            })
+            .unwrap(),
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: TOKEN_C.to_string(), msg: to_json_binary(&expected).unwrap(), funds: vec![] })
+        );
+    }
+
+    #[test]
+    fn test_execute_swap_operation_errors() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::ExecuteSwapOperation { operation: operations()[0].clone(), to: None };
+
+        // only the router may run a hop, anyone else could swap its balance away
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+
+        let err = execute_callback(&mut deps, msg).unwrap_err();
+        assert!(matches!(&err, ContractError::NothingToSwap { asset } if asset == DENOM_A), "{err}");
+
+        let msg = ExecuteMsg::ExecuteSwapOperation { operation: operation(native(DENOM_A), token(TOKEN_C)), to: None };
+        deps.querier.update_balance(mock_env().contract.address.as_str(), vec![coin(1_000, DENOM_A)]);
+        let err = execute_callback(&mut deps, msg).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+    }
+
+    #[test]
+    fn test_assert_minimum_receive() {
+        let mut deps = setup();
+        deps.querier.update_balance("alice", vec![coin(100, DENOM_B)]);
+        let operations = vec![operation(native(DENOM_A), native(DENOM_B))];
+        let msg = ExecuteMsg::ExecuteSwapOperations { operations, minimum_receive: Some(Uint128::new(800)), to: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[coin(1_000, DENOM_A)]), msg).unwrap();
+
+        // the check runs last, against the receiver's balance before the swaps
+        let assert_msg = callbacks(&res).pop().unwrap();
+        assert_eq!(
+            assert_msg,
+            ExecuteMsg::AssertMinimumReceive {
+                asset_info: native(DENOM_B),
+                prev_balance: Uint128::new(100),
+                minimum_receive: Uint128::new(800),
+                receiver: "alice".to_string(),
+            }
+        );
+
+        deps.querier.update_balance("alice", vec![coin(899, DENOM_B)]);
+        let err = execute_callback(&mut deps, assert_msg.clone()).unwrap_err();
+        assert!(
+            matches!(err, ContractError::AssertionMinimumReceive { minimum_receive, amount } if minimum_receive == Uint128::new(800) && amount == Uint128::new(799)),
+            "{err}"
+        );
+
+        deps.querier.update_balance("alice", vec![coin(900, DENOM_B)]);
+        let res = execute_callback(&mut deps, assert_msg).unwrap();
+        assert_eq!(attribute(&res, "amount"), "800");
+    }
+
+    #[test]
+    fn test_execute_swap_operations_first_offer() {
+        let mut deps = setup();
+
+        // a native first offer must come with the message, in that denom only
+        let err = execute_operations(&mut deps, &[], None, None).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::NoFunds {})), "{err}");
+        let err = execute_operations(&mut deps, &[coin(1_000, DENOM_B)], None, None).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::MissingDenom(ref denom)) if denom == DENOM_A), "{err}");
+        let err = execute_operations(&mut deps, &[coin(1_000, DENOM_A), coin(1_000, DENOM_B)], None, None).unwrap_err();
+        assert!(matches!(err, ContractError::Payment(PaymentError::MultipleDenoms {})), "{err}");
+
+        // a cw20 first offer must come through the Receive hook of that token
+        let cw20_operations = vec![operation(token(TOKEN_C), native(DENOM_B))];
+        let msg = ExecuteMsg::ExecuteSwapOperations { operations: cw20_operations.clone(), minimum_receive: None, to: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+        assert!(matches!(&err, ContractError::Cw20OfferNotReceived { asset } if asset == TOKEN_C), "{err}");
+
+        let hook = |operations: Vec<SwapOperation>| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "alice".to_string(),
+                amount: Uint128::new(500),
+                msg: to_json_binary(&Cw20HookMsg::ExecuteSwapOperations { operations, minimum_receive: None, to: None }).unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other_token", &[]), hook(cw20_operations.clone())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        let err = execute(deps.as_mut(), mock_env(), mock_info(TOKEN_C, &[]), hook(operations())).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(TOKEN_C, &[]), hook(cw20_operations.clone())).unwrap();
+        assert_eq!(attribute(&res, "receiver"), "alice");
+        assert_eq!(callbacks(&res), vec![ExecuteMsg::ExecuteSwapOperation { operation: cw20_operations[0].clone(), to: Some("alice".to_string()) }]);
+    }
+
+    #[test]
+    fn test_execute_swap_operations_to() {
+        let mut deps = setup();
+        let res = execute_operations(&mut deps, &[coin(1_000, DENOM_A)], Some(Uint128::new(1)), Some("bob")).unwrap();
+        assert_eq!(attribute(&res, "receiver"), "bob");
+
+        // the last hop pays bob, and the minimum is checked on bob's balance
+        let messages = callbacks(&res);
+        assert_eq!(messages[1], ExecuteMsg::ExecuteSwapOperation { operation: operations()[1].clone(), to: Some("bob".to_string()) });
+        assert_eq!(
+            messages[2],
+            ExecuteMsg::AssertMinimumReceive {
+                asset_info: token(TOKEN_C),
+                prev_balance: Uint128::zero(),
+                minimum_receive: Uint128::new(1),
+                receiver: "bob".to_string(),
+            }
+        );
+
+        let err = execute_operations(&mut deps, &[coin(1_000, DENOM_A)], None, Some("Bob")).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::GenericErr { .. })), "{err}");
+    }
+
+    #[test]
+    fn test_simulate_swap_operations() {
+        let deps = setup();
+
+        // each pair returns 90% of its offer: 1_000 -> 900 -> 810
+        let res = simulate_swap_operations(deps.as_ref(), Uint128::new(1_000), operations()).unwrap();
+        assert_eq!(res.amount, Uint128::new(810));
+        let res = simulate_swap_operations(deps.as_ref(), Uint128::new(1_000), operations()[..1].to_vec()).unwrap();
+        assert_eq!(res.amount, Uint128::new(900));
+
+        let err = simulate_swap_operations(deps.as_ref(), Uint128::new(1_000), vec![]).unwrap_err();
+        assert!(err.to_string().contains("Must provide swap operations"), "{err}");
+        let broken = vec![operation(native(DENOM_A), native(DENOM_B)), operation(token(TOKEN_C), native(DENOM_B))];
+        let err = simulate_swap_operations(deps.as_ref(), Uint128::new(1_000), broken).unwrap_err();
+        assert!(err.to_string().contains("uosmo is not offered by the next operation"), "{err}");
+    }
+}
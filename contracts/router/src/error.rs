@@ -0,0 +1,30 @@
+use cosmwasm_std::{StdError, Uint128};
+use cw_utils::PaymentError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("{asset} must be offered through the cw20 Receive hook")]
+    Cw20OfferNotReceived { asset: String },
+
+    #[error("Must provide swap operations")]
+    MustProvideOperations {},
+
+    #[error("Swap operations must chain, {ask_asset} is not offered by the next operation")]
+    InvalidOperations { ask_asset: String },
+
+    #[error("Nothing to swap, the router holds no {asset}")]
+    NothingToSwap { asset: String },
+
+    #[error("Received {amount}, expected at least {minimum_receive}")]
+    AssertionMinimumReceive { minimum_receive: Uint128, amount: Uint128 },
+}
@@ -0,0 +1,75 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::Uint128;
+use cw20::Cw20ReceiveMsg;
+use dex::asset::AssetInfo;
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub dex_factory: String,
+}
+
+// One hop: swaps offer_asset_info into ask_asset_info on the factory pair of the two
+#[cw_serde]
+pub struct SwapOperation {
+    pub offer_asset_info: AssetInfo,
+    pub ask_asset_info: AssetInfo,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    Receive(Cw20ReceiveMsg),
+    // native first offer only, cw20 offers go through Receive
+    ExecuteSwapOperations {
+        operations: Vec<SwapOperation>,
+        // reverts when the output of the last hop is below this
+        minimum_receive: Option<Uint128>,
+        // receives the output instead of the sender
+        to: Option<String>,
+    },
+    // internal, swaps the router's whole balance of the offer asset
+    ExecuteSwapOperation {
+        operation: SwapOperation,
+        to: Option<String>,
+    },
+    // internal, checks the receiver got at least minimum_receive since prev_balance
+    AssertMinimumReceive {
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        minimum_receive: Uint128,
+        receiver: String,
+    },
+}
+
+// Messages embedded in a cw20 Send to the router
+#[cw_serde]
+pub enum Cw20HookMsg {
+    ExecuteSwapOperations {
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+    },
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(ConfigResponse)]
+    Config {},
+
+    // output of the operations for offer_amount of the first offer asset, at the current reserves
+    #[returns(SimulateSwapOperationsResponse)]
+    SimulateSwapOperations {
+        offer_amount: Uint128,
+        operations: Vec<SwapOperation>,
+    },
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub dex_factory: String,
+}
+
+#[cw_serde]
+pub struct SimulateSwapOperationsResponse {
+    pub amount: Uint128,
+}
@@ -0,0 +1,12 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Addr;
+use cw_storage_plus::Item;
+
+#[cw_serde]
+pub struct Config {
+    // factory the pairs of the swap operations are looked up in
+    pub dex_factory: Addr,
+}
+
+//storage for the router config
+pub const CONFIG: Item<Config> = Item::new("config");
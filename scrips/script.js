@@ -127,7 +127,7 @@ async function main() {
     console.log(query_contract_info1);
 
     const query_msg3 = {
-      query_u_s_d_t_allowance: {
+      query_allowance: {
         user: sender
       }
     }
@@ -0,0 +1,143 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, Addr, Api, BankMsg, Coin, CosmosMsg, MessageInfo, QuerierWrapper, StdResult, Uint128, WasmMsg};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use std::fmt;
+use thiserror::Error;
+
+// A token of the pair: either a native bank denom or a cw20 contract
+#[cw_serde]
+pub enum AssetInfo {
+    NativeToken { denom: String },
+    Token { contract_addr: String },
+}
+
+impl AssetInfo {
+    pub fn is_native_token(&self) -> bool {
+        matches!(self, AssetInfo::NativeToken { .. })
+    }
+
+    pub fn check(&self, api: &dyn Api) -> StdResult<()> {
+        if let AssetInfo::Token { contract_addr } = self {
+            api.addr_validate(contract_addr)?;
+        }
+        Ok(())
+    }
+
+    // Balance of this token held by `address`
+    pub fn query_balance(&self, querier: &QuerierWrapper, address: impl Into<String>) -> StdResult<Uint128> {
+        match self {
+            AssetInfo::NativeToken { denom } => Ok(querier.query_balance(address, denom)?.amount),
+            AssetInfo::Token { contract_addr } => {
+                let response: BalanceResponse = querier.query_wasm_smart(contract_addr, &Cw20QueryMsg::Balance { address: address.into() })?;
+                Ok(response.balance)
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetInfo::NativeToken { denom } => write!(f, "{}", denom),
+            AssetInfo::Token { contract_addr } => write!(f, "{}", contract_addr),
+        }
+    }
+}
+
+// An amount of a given token
+#[cw_serde]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+impl Asset {
+    // Bank send or cw20 transfer of this amount to `recipient`
+    pub fn transfer_msg(&self, recipient: impl Into<String>) -> StdResult<CosmosMsg> {
+        match &self.info {
+            AssetInfo::NativeToken { denom } => Ok(CosmosMsg::Bank(BankMsg::Send {
+                to_address: recipient.into(),
+                amount: vec![Coin {
+                    denom: denom.to_string(),
+                    amount: self.amount,
+                }],
+            })),
+            AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.into(),
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            })),
+        }
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.info)
+    }
+}
+
+// A coin attached to a message that takes no coins of its denom
+#[derive(Error, Debug, PartialEq)]
+#[error("UnexpectedFunds: {denom} cannot be sent with this message")]
+pub struct UnexpectedFunds {
+    pub denom: String,
+}
+
+// Address the proceeds go to: `to` when given, the sender otherwise
+pub fn recipient(
+    api: &dyn Api,
+    to: Option<String>,
+    sender: &Addr,
+) -> StdResult<Addr> {
+    match to {
+        Some(to) => api.addr_validate(&to),
+        None => Ok(sender.clone()),
+    }
+}
+
+// Amount of the native `asset_info` attached to the message, zero for a cw20 token
+pub fn native_received(info: &MessageInfo, asset_info: &AssetInfo) -> Uint128 {
+    match asset_info {
+        AssetInfo::NativeToken { denom } => info
+            .funds
+            .iter()
+            .find(|coin| &coin.denom == denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default(),
+        AssetInfo::Token { .. } => Uint128::zero(),
+    }
+}
+
+// Rejects coins attached to the message that are not a native asset of the pool, they would
+// otherwise stay in the contract unaccounted for
+pub fn assert_expected_funds(
+    asset_infos: &[AssetInfo],
+    info: &MessageInfo,
+) -> Result<(), UnexpectedFunds> {
+    for coin in info.funds.iter() {
+        let expected = asset_infos
+            .iter()
+            .any(|asset_info| matches!(asset_info, AssetInfo::NativeToken { denom } if denom == &coin.denom));
+        if !expected {
+            return Err(UnexpectedFunds { denom: coin.denom.clone() });
+        }
+    }
+    Ok(())
+}
+
+// Swaps take only the offer asset: coins of the ask denom would be neither swapped nor refunded
+pub fn assert_offer_funds(
+    offer_info: &AssetInfo,
+    info: &MessageInfo,
+) -> Result<(), UnexpectedFunds> {
+    for coin in info.funds.iter() {
+        if !matches!(offer_info, AssetInfo::NativeToken { denom } if denom == &coin.denom) {
+            return Err(UnexpectedFunds { denom: coin.denom.clone() });
+        }
+    }
+    Ok(())
+}
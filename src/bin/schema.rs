@@ -1,6 +1,6 @@
 use cosmwasm_schema::write_api;
 
-use dex::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use dex::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 
 fn main() {
@@ -8,5 +8,6 @@ fn main() {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        migrate: MigrateMsg,
     }
 }
@@ -1,567 +1,2656 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128, WasmMsg};
-use cw20::{AllowanceResponse, Cw20ExecuteMsg};
-// use crate::error::ContractError;
-use crate::msg::{ContractInfoResponse, ExecuteMsg, InstantiateMsg, LptBalanceResponse, PoolInfoResponse, QueryMsg, USDTAllowanceResponse};
-use crate::state::{ContractInfo, LiquidityPool, INFO, LIQUIDITY_PROVIDERS, POOL, USDT_ALLOWANCE};
-use std::str::FromStr;
-
-const DENOM_ORAI: &str = "orai";
-const DENOM_USDT: &str = "usdt";
-
-pub fn sqrt(value: Decimal) -> Decimal {
-    if value.is_zero() {
-        return Decimal::zero();
-    }
-    value.sqrt()
-}
+use cosmwasm_std::{from_json, to_json_binary, Addr, Binary, CosmosMsg, Decimal, Decimal256, Deps, DepsMut, Env, MessageInfo, QuerierWrapper, Reply, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128, Uint256, WasmMsg};
+use cw20::{AllowanceResponse, Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw20_base::msg::InstantiateMsg as TokenInstantiateMsg;
+use cw2::{get_contract_version, set_contract_version};
+use cw_utils::{nonpayable, parse_reply_instantiate_data};
+use semver::Version;
+use crate::error::ContractError;
+use crate::asset::{assert_expected_funds, assert_offer_funds, native_received, recipient, Asset, AssetInfo};
+use crate::math;
+use crate::migration;
+use crate::oracle;
+use crate::stableswap;
+use crate::weighted;
+use crate::msg::{AllowanceResponse as PoolAllowanceResponse, AmpResponse, ContractInfoResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, LptBalanceResponse, MigrateMsg, OwnershipProposalResponse, PauseStatusResponse, PoolInfoResponse, QueryMsg, ReverseSimulationResponse, SimulationResponse, SpotPriceResponse, TwapResponse};
+use crate::state::{AmpConfig, ContractInfo, LiquidityPool, Observation, OwnershipProposal, PairType, PauseMode, AMP_CONFIG, DEPOSITS, INFO, OWNERSHIP_PROPOSAL, PAUSE_MODE, POOL};
+
+// version info for migration
+const CONTRACT_NAME: &str = "crates.io:dex";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const INSTANTIATE_LP_TOKEN_REPLY_ID: u64 = 1;
+
+// 0.3% fee on the offer amount, left in the pool for LPs, unless set at instantiation
+pub const DEFAULT_LP_FEE_BPS: u16 = 30;
+
+// Upper bounds the owner cannot set the fees above
+pub const MAX_LP_FEE_BPS: u16 = 100;
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 50;
+
+// Longest time a proposed owner has to claim the ownership, 14 days
+pub const MAX_OWNERSHIP_PROPOSAL_EXPIRY: u64 = 14 * 86_400;
+
+// LP tokens minted to the pair itself on the first deposit and never withdrawable, so the
+// share price cannot be inflated from a dust-sized supply
+pub const MINIMUM_LIQUIDITY: Uint128 = Uint128::new(1_000);
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    info: MessageInfo,
+    env: Env,
+    _info: MessageInfo,
     msg: InstantiateMsg,
-) -> Result<Response, StdError> {
+) -> Result<Response, ContractError> {
+    if msg.asset_infos[0] == msg.asset_infos[1] {
+        return Err(ContractError::InvalidTokenPair {});
+    }
+    msg.asset_infos[0].check(deps.api)?;
+    msg.asset_infos[1].check(deps.api)?;
+    let owner = deps.api.addr_validate(&msg.owner)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let lp_fee_bps = msg.lp_fee_bps.unwrap_or(DEFAULT_LP_FEE_BPS);
+    let protocol_fee_bps = msg.protocol_fee_bps.unwrap_or_default();
+    let fee_collector = msg.fee_collector.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    assert_fees(lp_fee_bps, protocol_fee_bps, &fee_collector)?;
+
+    let pair_type = msg.pair_type.unwrap_or(PairType::Xyk {});
+    let weights = match &pair_type {
+        PairType::Weighted { weights } => {
+            weighted::assert_weights(weights)?;
+            *weights
+        }
+        _ => [Decimal::percent(50), Decimal::percent(50)],
+    };
+    if pair_type == (PairType::Stable {}) {
+        let amp = msg.amp.ok_or(ContractError::AmpMissing {})?;
+        assert_amp(amp)?;
+        let now = env.block.time.seconds();
+        AMP_CONFIG.save(deps.storage, &AmpConfig {
+            init_amp: amp,
+            init_amp_time: now,
+            next_amp: amp,
+            next_amp_time: now,
+        })?;
+    }
+
+    // lpt_contract is filled in by the reply once the LP token is instantiated
     let contract_info = ContractInfo {
-        owner: info.sender.clone(),
-        usdt_contract: msg.usdt_contract.clone(),
-        lpt_contract: msg.lpt_contract.clone(),
+        owner: owner.clone(),
+        asset_infos: msg.asset_infos.clone(),
+        lpt_contract: String::new(),
+        lp_fee_bps,
+        protocol_fee_bps,
+        fee_collector,
+        pair_type,
+        guardian: None,
     };
 
     let pool = LiquidityPool {
-        orai_reserve: Uint128::zero(),
-        usdt_reserve: Uint128::zero(),
+        reserves: [Uint128::zero(), Uint128::zero()],
         total_shares: Uint128::zero(),
+        price0_cumulative_last: Uint256::zero(),
+        price1_cumulative_last: Uint256::zero(),
+        block_time_last: env.block.time.seconds(),
+        weights,
     };
 
     INFO.save(deps.storage, &contract_info)?;
     POOL.save(deps.storage, &pool)?;
+    oracle::push_observation(deps.storage, &Observation {
+        timestamp: pool.block_time_last,
+        price0_cumulative: pool.price0_cumulative_last,
+        price1_cumulative: pool.price1_cumulative_last,
+    })?;
+
+    // the pair is the sole minter of its LP token
+    let instantiate_lpt_msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: msg.token_code_id,
+        msg: to_json_binary(&TokenInstantiateMsg {
+            name: "dex liquidity token".to_string(),
+            symbol: "uLP".to_string(),
+            decimals: 6,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: env.contract.address.to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        })?,
+        funds: vec![],
+        label: format!("dex lp token {}-{}", msg.asset_infos[0], msg.asset_infos[1]),
+    };
 
     Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate_lpt_msg, INSTANTIATE_LP_TOKEN_REPLY_ID))
         .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender)
-        .add_attribute("usdt_contract", msg.usdt_contract)
-        .add_attribute("lpt_contract", msg.lpt_contract))
+        .add_attribute("owner", owner)
+        .add_attribute("asset_0", msg.asset_infos[0].to_string())
+        .add_attribute("asset_1", msg.asset_infos[1].to_string())
+        .add_attribute("token_code_id", msg.token_code_id.to_string())
+        .add_attribute("lp_fee_bps", lp_fee_bps.to_string())
+        .add_attribute("protocol_fee_bps", protocol_fee_bps.to_string()))
 }
 
+// Runs the storage migrations between the stored version and this one. The first deployment has
+// no stored version and gets its ORAI/USDT layout moved to the generic one.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn execute(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    msg: ExecuteMsg,
-) -> Result<Response, StdError> {
-    match msg {
-        ExecuteMsg::AddLiquidity { orai_amount, usdt_amount } => add_liquidity(deps, env, info, orai_amount, usdt_amount),
-        ExecuteMsg::RemoveLiquidity { lpt_amount } => remove_liquidity(deps, env, info, lpt_amount),
-        ExecuteMsg::Swap { denom, amount } => swap(deps, env, info, denom, amount),
-    }
-}
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version: Version = CONTRACT_VERSION.parse()?;
 
-pub fn query_cw20_token_allowance(
-    deps: &DepsMut,
-    owner: String,
-    spender: String,
-    token_contract: &String,
-) -> StdResult<Uint128> {
-    let response: AllowanceResponse = deps.querier.query(&cosmwasm_std::QueryRequest::Wasm(cosmwasm_std::WasmQuery::Smart {
-        contract_addr: token_contract.to_string(),
-        msg: to_json_binary(&cw20::Cw20QueryMsg::Allowance { owner, spender })?,
-    }))?;
-    Ok(response.allowance)
+    let from_version = if migration::is_legacy(deps.storage)? {
+        migration::migrate_legacy_pool(deps.storage, &env)?;
+        "legacy".to_string()
+    } else {
+        let stored = get_contract_version(deps.storage)?;
+        if stored.contract != CONTRACT_NAME {
+            return Err(ContractError::InvalidMigration { contract: stored.contract, expected: CONTRACT_NAME.to_string() });
+        }
+        let stored_version: Version = stored.version.parse()?;
+        if stored_version > version {
+            return Err(ContractError::MigrationDowngrade { stored: stored.version, version: CONTRACT_VERSION.to_string() });
+        }
+        stored.version
+    };
+    migration::remove_liquidity_providers(deps.storage)?;
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", from_version)
+        .add_attribute("to_version", CONTRACT_VERSION))
 }
 
-pub fn calculate_swap_amount(
-    pool: &LiquidityPool,
-    denom: &String,
-    amount: Uint128,
-) -> StdResult<Uint128> {
-    if denom.as_str() == DENOM_ORAI {
-        let orai_reserve = pool.orai_reserve.u128();
-        let usdt_reserve = pool.usdt_reserve.u128();
-        let amount_with_fee = amount.u128() * 997 / 1000; // 0.3% fee
-        let numerator = amount_with_fee * usdt_reserve;
-        let denominator = orai_reserve + amount_with_fee;
-        Ok(Uint128::from(numerator / denominator))
-    } else if denom.as_str() == DENOM_USDT {
-        let orai_reserve = pool.orai_reserve.u128();
-        let usdt_reserve = pool.usdt_reserve.u128();
-        let amount_with_fee = amount.u128() * 997 / 1000; // 0.3% fee
-        let numerator = amount_with_fee * orai_reserve;
-        let denominator = usdt_reserve + amount_with_fee;
-        Ok(Uint128::from(numerator / denominator))
-    } else {
-        Err(StdError::generic_err("calculate_swap_amount: Unsupported token pair"))
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        INSTANTIATE_LP_TOKEN_REPLY_ID => register_lp_token(deps, msg),
+        id => Err(ContractError::UnknownReplyId { id }),
     }
 }
 
-pub fn transfer_orai(
-    recipient: String,
-    amount: Uint128,
-) -> StdResult<CosmosMsg> {
-    if amount.is_zero() {
-        return Err(StdError::generic_err("transfer_orai: Amount must be greater than zero"));
-    }
-    Ok(CosmosMsg::Bank(BankMsg::Send {
-        to_address: recipient,
-        amount: vec![Coin {
-            denom: DENOM_ORAI.to_string(),
-            amount,
-        }],
-    }))
-}
+pub fn register_lp_token(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let res = parse_reply_instantiate_data(msg)?;
 
-pub fn transfer_usdt(
-    deps: &DepsMut,
-    recipient: String,
-    amount: Uint128,
-) -> StdResult<CosmosMsg> {
-    if amount.is_zero() {
-        return Err(StdError::generic_err("transfer_usdt: Amount must be greater than zero"));
+    let mut contract_info = INFO.load(deps.storage)?;
+    if !contract_info.lpt_contract.is_empty() {
+        return Err(ContractError::LpTokenAlreadyRegistered { lpt_contract: contract_info.lpt_contract });
     }
-    let contract_info = INFO.load(deps.storage)?;
-    let transfer_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: contract_info.usdt_contract.to_string(),
-        msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient, amount })?,
-        funds: vec![],
-    });
-    Ok(transfer_msg)
+    contract_info.lpt_contract = deps.api.addr_validate(&res.contract_address)?.to_string();
+    INFO.save(deps.storage, &contract_info)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_lp_token")
+        .add_attribute("lpt_contract", contract_info.lpt_contract))
 }
 
-pub fn transfer_token(
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
     deps: DepsMut,
-    denom: String,
-    recipient: String,
-    amount: Uint128,
-) -> StdResult<CosmosMsg> {
-    let contract_info = INFO.load(deps.storage)?;
-    if denom.as_str() == DENOM_USDT {
-        let transfer_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_info.usdt_contract.to_string(),
-            msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient, amount })?,
-            funds: vec![],
-        });
-        Ok(transfer_msg)
-    } else if denom.as_str() == DENOM_ORAI {
-        transfer_orai(recipient, amount)
-    } else {
-        Err(StdError::generic_err("transfer_token: Unsupported token denom"))
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    // cw20 hooks are checked once decoded, admin messages are never paused
+    let operation = match &msg {
+        ExecuteMsg::AddLiquidity { .. } => Some(Operation::Deposit),
+        ExecuteMsg::RemoveLiquidity { .. } | ExecuteMsg::WithdrawDeposits {} => Some(Operation::Withdrawal),
+        // swaps the withdrawn leg, so it stops with swaps
+        ExecuteMsg::WithdrawSingleSided { .. } => Some(Operation::Swap),
+        ExecuteMsg::Swap { .. } | ExecuteMsg::SwapExactOut { .. } => Some(Operation::Swap),
+        _ => None,
+    };
+    if let Some(operation) = operation {
+        assert_not_paused(deps.storage, operation)?;
+    }
+
+    match msg {
+        ExecuteMsg::AddLiquidity { assets, slippage_tolerance, min_lp_out, deadline } => {
+            assert_deadline(&env, deadline)?;
+            add_liquidity(deps, env, info, assets, slippage_tolerance, min_lp_out)
+        }
+        ExecuteMsg::RemoveLiquidity { lpt_amount, min_assets, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            remove_liquidity(deps, env, info, lpt_amount, WithdrawMode::Balanced { min_assets }, to)
+        }
+        ExecuteMsg::WithdrawSingleSided { lpt_amount, ask_asset_info, min_output, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            remove_liquidity(deps, env, info, lpt_amount, WithdrawMode::SingleSided { ask_asset_info, min_output }, to)
+        }
+        ExecuteMsg::Swap { offer_asset, min_output, belief_price, max_spread, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            if !offer_asset.info.is_native_token() {
+                return Err(ContractError::Cw20OfferNotReceived { asset: offer_asset.info.to_string() });
+            }
+            let receiver = recipient(deps.api, to, &info.sender)?;
+            swap(deps, env, info, receiver, offer_asset, min_output, belief_price, max_spread)
+        }
+        ExecuteMsg::SwapExactOut { ask_asset, max_offer, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            let contract_info = INFO.load(deps.storage)?;
+            let offer_info = contract_info.asset_infos[1 - asset_index(&contract_info, &ask_asset.info)?].clone();
+            if !offer_info.is_native_token() {
+                return Err(ContractError::Cw20OfferNotReceived { asset: offer_info.to_string() });
+            }
+            assert_offer_funds(&offer_info, &info)?;
+            let offer_budget = Asset {
+                amount: provided_amount(&deps, &info, &offer_info)?,
+                info: offer_info,
+            };
+            let receiver = recipient(deps.api, to, &info.sender)?;
+            let sender = info.sender.clone();
+            swap_exact_out(deps, env, sender, receiver, offer_budget, ask_asset, max_offer)
+        }
+        ExecuteMsg::WithdrawDeposits {} => withdraw_deposits(deps, info),
+        ExecuteMsg::UpdateConfig { lp_fee_bps, protocol_fee_bps, fee_collector, guardian } => {
+            update_config(deps, info, lp_fee_bps, protocol_fee_bps, fee_collector, guardian)
+        }
+        ExecuteMsg::RemoveGuardian {} => remove_guardian(deps, info),
+        ExecuteMsg::SetPauseMode { mode } => set_pause_mode(deps, info, mode),
+        ExecuteMsg::ProposeNewOwner { owner, expires_in } => propose_new_owner(deps, env, info, owner, expires_in),
+        ExecuteMsg::ClaimOwnership {} => claim_ownership(deps, env, info),
+        ExecuteMsg::DropOwnershipProposal {} => drop_ownership_proposal(deps, info),
+        ExecuteMsg::RampAmp { next_amp, next_amp_time } => ramp_amp(deps, env, info, next_amp, next_amp_time),
+        ExecuteMsg::StopRampAmp {} => stop_ramp_amp(deps, env, info),
+        ExecuteMsg::Receive(cw20_msg) => receive_cw20(deps, env, info, cw20_msg),
     }
 }
 
-pub fn add_liquidity(
+pub fn receive_cw20(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    orai_amount: Uint128,
-    usdt_amount: Uint128,
-) -> Result<Response, StdError> {
-    let contract_info = INFO.load(deps.storage)?;
-
-    if orai_amount.is_zero() || usdt_amount.is_zero() {
-        return Err(StdError::generic_err("add_liquidity: ORAI or USDT amount cannot be zero"));
-    }
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+    // info.sender is the cw20 contract that forwarded the tokens
+    let received = Asset {
+        info: AssetInfo::Token { contract_addr: info.sender.to_string() },
+        amount: cw20_msg.amount,
+    };
 
-    let received_orai = info
-        .funds
-        .iter()
-        .find(|coin| coin.denom == DENOM_ORAI)
-        .map(|coin| coin.amount)
-        .unwrap_or_default();
+    let hook_msg: Cw20HookMsg = from_json(&cw20_msg.msg)?;
+    let operation = match &hook_msg {
+        Cw20HookMsg::Swap { .. } | Cw20HookMsg::SwapExactOut { .. } | Cw20HookMsg::WithdrawSingleSided { .. } => Operation::Swap,
+        Cw20HookMsg::ProvideLiquidity {} => Operation::Deposit,
+        Cw20HookMsg::WithdrawLiquidity { .. } => Operation::Withdrawal,
+    };
+    assert_not_paused(deps.storage, operation)?;
 
-    if received_orai < orai_amount {
-        return Err(StdError::generic_err(format!(
-            "add_liquidity: Insufficient ORAI received. Expected: {}, Received: {}",
-            orai_amount, received_orai
-        )));
+    match hook_msg {
+        Cw20HookMsg::Swap { min_output, belief_price, max_spread, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            let receiver = recipient(deps.api, to, &sender)?;
+            swap(deps, env, info, receiver, received, min_output, belief_price, max_spread)
+        }
+        Cw20HookMsg::SwapExactOut { ask_asset, max_offer, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            let max_offer = max_offer.unwrap_or(received.amount);
+            let receiver = recipient(deps.api, to, &sender)?;
+            swap_exact_out(deps, env, sender, receiver, received, ask_asset, max_offer)
+        }
+        Cw20HookMsg::ProvideLiquidity {} => deposit_token(deps, sender, received),
+        Cw20HookMsg::WithdrawLiquidity { min_assets, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            let receiver = recipient(deps.api, to, &sender)?;
+            receive_lp_token(deps, env, info, sender, receiver, cw20_msg.amount, WithdrawMode::Balanced { min_assets })
+        }
+        Cw20HookMsg::WithdrawSingleSided { ask_asset_info, min_output, deadline, to } => {
+            assert_deadline(&env, deadline)?;
+            let receiver = recipient(deps.api, to, &sender)?;
+            receive_lp_token(deps, env, info, sender, receiver, cw20_msg.amount, WithdrawMode::SingleSided { ask_asset_info, min_output })
+        }
     }
+}
 
-    let mut pool = POOL.load(deps.storage)?;
-    if pool.orai_reserve.is_zero() || pool.usdt_reserve.is_zero() {
-        pool.orai_reserve += orai_amount;
-        pool.usdt_reserve += usdt_amount;
+pub fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    lp_fee_bps: Option<u16>,
+    protocol_fee_bps: Option<u16>,
+    fee_collector: Option<String>,
+    guardian: Option<String>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let mut contract_info = INFO.load(deps.storage)?;
+    if info.sender != contract_info.owner {
+        return Err(ContractError::Unauthorized {});
+    }
 
-        let a = Decimal::from_atomics(orai_amount, 0).unwrap();
-        let b = Decimal::from_atomics(usdt_amount, 0).unwrap();
-        let divisor = Decimal::from_str("3.14918").unwrap();
-        let lpt_mint_decimal = sqrt(a) * sqrt(b) / divisor;
-        let lpt_mint = lpt_mint_decimal.to_uint_floor(); // Sửa từ to_uint_floor trên Uint128 sang Decimal
+    if let Some(fee_collector) = fee_collector {
+        contract_info.fee_collector = Some(deps.api.addr_validate(&fee_collector)?);
+    }
+    if let Some(guardian) = guardian {
+        contract_info.guardian = Some(deps.api.addr_validate(&guardian)?);
+    }
+    contract_info.lp_fee_bps = lp_fee_bps.unwrap_or(contract_info.lp_fee_bps);
+    contract_info.protocol_fee_bps = protocol_fee_bps.unwrap_or(contract_info.protocol_fee_bps);
+    assert_fees(contract_info.lp_fee_bps, contract_info.protocol_fee_bps, &contract_info.fee_collector)?;
+    INFO.save(deps.storage, &contract_info)?;
 
-        pool.total_shares += lpt_mint;
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("lp_fee_bps", contract_info.lp_fee_bps.to_string())
+        .add_attribute("protocol_fee_bps", contract_info.protocol_fee_bps.to_string())
+        .add_attribute("fee_collector", contract_info.fee_collector.map(|addr| addr.to_string()).unwrap_or_default())
+        .add_attribute("guardian", contract_info.guardian.map(|addr| addr.to_string()).unwrap_or_default()))
+}
 
-        let mint_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_info.lpt_contract.to_string(),
-            msg: to_json_binary(&Cw20ExecuteMsg::Mint {
-                recipient: info.sender.to_string(),
-                amount: lpt_mint,
-            })?,
-            funds: vec![],
-        });
+pub fn remove_guardian(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let mut contract_info = INFO.load(deps.storage)?;
+    if info.sender != contract_info.owner {
+        return Err(ContractError::Unauthorized {});
+    }
 
-        let sender = info.sender.clone();
-        let current_lpt_balance = LIQUIDITY_PROVIDERS.may_load(deps.storage, &sender)?.unwrap_or_default();
-        let new_lpt_balance = current_lpt_balance + lpt_mint;
+    let previous_guardian = contract_info.guardian.take();
+    INFO.save(deps.storage, &contract_info)?;
 
-        let usdt_allowance = query_cw20_token_allowance(&deps, info.sender.to_string(), env.contract.address.to_string(), &contract_info.usdt_contract.to_string())?;
-        if usdt_allowance < usdt_amount {
-            return Err(StdError::generic_err(format!(
-                "add_liquidity: Insufficient USDT allowance. Required: {}, Available: {}",
-                usdt_amount, usdt_allowance
-            )));
-        }
+    Ok(Response::new()
+        .add_attribute("action", "remove_guardian")
+        .add_attribute("previous_guardian", previous_guardian.map(|addr| addr.to_string()).unwrap_or_default()))
+}
 
-        let transfer_from_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_info.usdt_contract.to_string(),
-            msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
-                owner: sender.to_string(),
-                recipient: env.contract.address.to_string(),
-                amount: usdt_amount,
-            })?,
-            funds: vec![],
-        });
+// What a message does to the pool, for the pause mode to allow or not
+#[derive(Clone, Copy)]
+enum Operation {
+    Swap,
+    Deposit,
+    Withdrawal,
+}
 
-        USDT_ALLOWANCE.save(deps.storage, &sender, &usdt_allowance)?;
-        POOL.save(deps.storage, &pool)?;
-        LIQUIDITY_PROVIDERS.save(deps.storage, &sender, &new_lpt_balance)?;
-
-        return Ok(Response::new()
-            .add_message(mint_msg)
-            .add_message(transfer_from_msg)
-            .add_attribute("action", "add_liquidity")
-            .add_attribute("orai_amount", orai_amount.to_string())
-            .add_attribute("usdt_amount", usdt_amount.to_string())
-            .add_attribute("lpt_mint", lpt_mint.to_string()));
-    }
-
-    let pool_ratio = Decimal::from_ratio(pool.orai_reserve, pool.usdt_reserve);
-    let input_ratio = Decimal::from_ratio(orai_amount, usdt_amount);
-
-    let (orai_to_use, usdt_to_use, unused_orai, unused_usdt) = if input_ratio > pool_ratio {
-        let usdt_to_use = usdt_amount;
-        let orai_to_use = (pool_ratio * Decimal::from_atomics(usdt_to_use, 0).unwrap()).to_uint_floor();
-        let unused_orai = orai_amount - orai_to_use;
-        (orai_to_use, usdt_to_use, unused_orai, Uint128::zero())
-    } else if input_ratio < pool_ratio {
-        let orai_to_use = orai_amount;
-        let usdt_to_use = (Decimal::from_atomics(orai_to_use, 0).unwrap() * Decimal::from_ratio(pool.usdt_reserve, pool.orai_reserve)).to_uint_floor();
-        let unused_usdt = usdt_amount - usdt_to_use;
-        (orai_to_use, usdt_to_use, Uint128::zero(), unused_usdt)
-    } else {
-        (orai_amount, usdt_amount, Uint128::zero(), Uint128::zero())
+fn assert_not_paused(storage: &dyn Storage, operation: Operation) -> Result<(), ContractError> {
+    let mode = PAUSE_MODE.may_load(storage)?.unwrap_or(PauseMode::Unpaused {});
+    let (blocked, name) = match operation {
+        Operation::Swap => (mode.blocks_swaps(), "swaps"),
+        Operation::Deposit => (mode.blocks_deposits(), "deposits"),
+        Operation::Withdrawal => (mode.blocks_withdrawals(), "withdrawals"),
     };
-
-    let usdt_allowance = query_cw20_token_allowance(&deps, info.sender.to_string(), env.contract.address.to_string(), &contract_info.usdt_contract.to_string())?;
-    if usdt_allowance < usdt_to_use {
-        return Err(StdError::generic_err(format!(
-            "add_liquidity: Insufficient USDT allowance for non-empty pool. Required: {}, Available: {}",
-            usdt_to_use, usdt_allowance
-        )));
+    if blocked {
+        return Err(ContractError::Paused { operation: name.to_string() });
     }
+    Ok(())
+}
 
-    let lpt_mint = (Decimal::from_atomics(orai_to_use * pool.total_shares / pool.orai_reserve, 0).unwrap()).to_uint_floor(); // Sửa: Chuyển đổi Decimal sang Uint128
-    pool.orai_reserve += orai_to_use;
-    pool.usdt_reserve += usdt_to_use;
-    pool.total_shares += lpt_mint;
+pub fn set_pause_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: PauseMode,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let contract_info = INFO.load(deps.storage)?;
+    if info.sender != contract_info.owner && Some(&info.sender) != contract_info.guardian.as_ref() {
+        return Err(ContractError::Unauthorized {});
+    }
+    PAUSE_MODE.save(deps.storage, &mode)?;
 
-    let sender = info.sender.clone();
-    let current_lpt_balance = LIQUIDITY_PROVIDERS.may_load(deps.storage, &sender)?.unwrap_or_default();
-    let new_lpt_balance = current_lpt_balance + lpt_mint;
+    Ok(Response::new()
+        .add_attribute("action", "set_pause_mode")
+        .add_attribute("sender", info.sender)
+        .add_attribute("mode", mode.to_string()))
+}
 
-    let mint_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: contract_info.lpt_contract.to_string(),
-        msg: to_json_binary(&Cw20ExecuteMsg::Mint {
-            recipient: info.sender.to_string(),
-            amount: lpt_mint,
-        })?,
-        funds: vec![],
-    });
+// Ownership moves in two steps so that it cannot be handed to an address nobody controls
+pub fn propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    expires_in: u64,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let contract_info = INFO.load(deps.storage)?;
+    if info.sender != contract_info.owner {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    let transfer_from_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: contract_info.usdt_contract.to_string(),
-        msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
-            owner: sender.to_string(),
-            recipient: env.contract.address.to_string(),
-            amount: usdt_to_use,
-        })?,
-        funds: vec![],
-    });
+    let new_owner = deps.api.addr_validate(&owner)?;
+    if new_owner == contract_info.owner || expires_in == 0 || expires_in > MAX_OWNERSHIP_PROPOSAL_EXPIRY {
+        return Err(ContractError::InvalidOwnershipProposal { max_expires_in: MAX_OWNERSHIP_PROPOSAL_EXPIRY });
+    }
+    let expires_at = env.block.time.plus_seconds(expires_in);
+    OWNERSHIP_PROPOSAL.save(deps.storage, &OwnershipProposal { owner: new_owner.clone(), expires_at })?;
 
-    let mut response = Response::new()
-        .add_message(transfer_from_msg)
-        .add_message(mint_msg)
-        .add_attribute("action", "add_liquidity")
-        .add_attribute("orai_amount", orai_to_use.to_string())
-        .add_attribute("usdt_amount", usdt_to_use.to_string())
-        .add_attribute("lpt_mint", lpt_mint.to_string());
+    Ok(Response::new()
+        .add_attribute("action", "propose_new_owner")
+        .add_attribute("proposed_owner", new_owner)
+        .add_attribute("expires_at", expires_at.to_string()))
+}
 
-    if !unused_orai.is_zero() {
-        let msg_transfer_unused_orai = transfer_orai(info.sender.to_string(), unused_orai)?;
-        response = response.add_message(msg_transfer_unused_orai);
+pub fn claim_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let proposal = OWNERSHIP_PROPOSAL.may_load(deps.storage)?.ok_or(ContractError::OwnershipProposalNotFound {})?;
+    if info.sender != proposal.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if env.block.time > proposal.expires_at {
+        return Err(ContractError::OwnershipProposalExpired { expires_at: proposal.expires_at });
     }
 
-    // if !unused_usdt.is_zero() {
-    //     let msg_transfer_unused_usdt = transfer_usdt(&deps, info.sender.to_string(), unused_usdt)?;
-    //     response = response.add_message(msg_transfer_unused_usdt);
-    // }
-
-    USDT_ALLOWANCE.save(deps.storage, &sender, &usdt_allowance)?;
-    LIQUIDITY_PROVIDERS.save(deps.storage, &sender, &new_lpt_balance)?;
-    POOL.save(deps.storage, &pool)?;
+    let mut contract_info = INFO.load(deps.storage)?;
+    let previous_owner = contract_info.owner;
+    contract_info.owner = proposal.owner;
+    INFO.save(deps.storage, &contract_info)?;
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
 
-    Ok(response)
+    Ok(Response::new()
+        .add_attribute("action", "claim_ownership")
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("new_owner", contract_info.owner))
 }
 
-pub fn remove_liquidity(
+pub fn drop_ownership_proposal(
     deps: DepsMut,
-    env: Env,
     info: MessageInfo,
-    lpt_amount: Uint128,
-) -> Result<Response, StdError> {
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
     let contract_info = INFO.load(deps.storage)?;
-
-    if lpt_amount.is_zero() {
-        return Err(StdError::generic_err("remove_liquidity: LPT amount cannot be zero"));
+    if info.sender != contract_info.owner {
+        return Err(ContractError::Unauthorized {});
     }
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
 
-    let approved_lpt = query_cw20_token_allowance(
-        &deps,
-        info.sender.to_string(),
-        env.contract.address.to_string(),
-        &contract_info.lpt_contract.to_string(),
-    )?;
-    if approved_lpt < lpt_amount {
-        return Err(StdError::generic_err(format!(
-            "remove_liquidity: Insufficient LPT allowance. Required: {}, Available: {}",
-            lpt_amount, approved_lpt
-        )));
-    }
+    Ok(Response::new().add_attribute("action", "drop_ownership_proposal"))
+}
 
-    let sender = info.sender.clone();
-    let current_lpt_balance = LIQUIDITY_PROVIDERS.may_load(deps.storage, &sender)?.unwrap_or_default();
-    if current_lpt_balance < lpt_amount {
-        return Err(StdError::generic_err(format!(
-            "remove_liquidity: Insufficient LPT balance. Required: {}, Available: {}",
-            lpt_amount, current_lpt_balance
-        )));
+pub fn assert_fees(
+    lp_fee_bps: u16,
+    protocol_fee_bps: u16,
+    fee_collector: &Option<Addr>,
+) -> Result<(), ContractError> {
+    if lp_fee_bps > MAX_LP_FEE_BPS || protocol_fee_bps > MAX_PROTOCOL_FEE_BPS {
+        return Err(ContractError::InvalidFee {
+            lp_fee_bps,
+            protocol_fee_bps,
+            max_lp_fee_bps: MAX_LP_FEE_BPS,
+            max_protocol_fee_bps: MAX_PROTOCOL_FEE_BPS,
+        });
     }
-
-    let mut pool = POOL.load(deps.storage)?;
-    if pool.total_shares.is_zero() {
-        return Err(StdError::generic_err("remove_liquidity: Pool has no shares"));
+    if protocol_fee_bps > 0 && fee_collector.is_none() {
+        return Err(ContractError::FeeCollectorNotSet {});
     }
+    Ok(())
+}
 
-    let orai_amount = Uint128::from(lpt_amount * pool.orai_reserve / pool.total_shares);
-    let usdt_amount = Uint128::from(lpt_amount * pool.usdt_reserve / pool.total_shares);
-
-    if pool.orai_reserve < orai_amount || pool.usdt_reserve < usdt_amount {
-        return Err(StdError::generic_err(format!(
-            "remove_liquidity: Insufficient liquidity. ORAI required: {}, Available: {}. USDT required: {}, Available: {}",
-            orai_amount, pool.orai_reserve, usdt_amount, pool.usdt_reserve
-        )));
+fn assert_amp(amp: u64) -> Result<(), ContractError> {
+    if !(stableswap::MIN_AMP..=stableswap::MAX_AMP).contains(&amp) {
+        return Err(ContractError::InvalidAmp { amp, min_amp: stableswap::MIN_AMP, max_amp: stableswap::MAX_AMP });
     }
+    Ok(())
+}
 
-    pool.orai_reserve -= orai_amount;
-    pool.usdt_reserve -= usdt_amount;
-    pool.total_shares -= lpt_amount;
-
-    POOL.save(deps.storage, &pool)?;
+fn load_stable_amp(
+    deps: &DepsMut,
+    info: &MessageInfo,
+) -> Result<AmpConfig, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+    if info.sender != contract_info.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if contract_info.pair_type != (PairType::Stable {}) {
+        return Err(ContractError::NotStablePair {});
+    }
+    Ok(AMP_CONFIG.load(deps.storage)?)
+}
 
-    let new_lpt_balance = current_lpt_balance - lpt_amount;
-    LIQUIDITY_PROVIDERS.save(deps.storage, &sender, &new_lpt_balance)?;
+pub fn current_amp(amp_config: &AmpConfig, time: u64) -> u64 {
+    stableswap::current_amp(amp_config.init_amp, amp_config.init_amp_time, amp_config.next_amp, amp_config.next_amp_time, time)
+}
 
-    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: contract_info.lpt_contract.to_string(),
-        msg: to_json_binary(&Cw20ExecuteMsg::BurnFrom {
-            owner: info.sender.to_string(),
-            amount: lpt_amount,
-        })?,
-        funds: vec![],
-    });
+// Starts a ramp from the current amp, by at most MAX_AMP_CHANGE times over at least MIN_RAMP_TIME
+pub fn ramp_amp(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    next_amp: u64,
+    next_amp_time: u64,
+) -> Result<Response, ContractError> {
+    let amp_config = load_stable_amp(&deps, &info)?;
+    assert_amp(next_amp)?;
+
+    let now = env.block.time.seconds();
+    let amp = current_amp(&amp_config, now);
+    let too_fast = next_amp_time < now + stableswap::MIN_RAMP_TIME
+        || next_amp > amp * stableswap::MAX_AMP_CHANGE
+        || next_amp * stableswap::MAX_AMP_CHANGE < amp;
+    if too_fast {
+        return Err(ContractError::InvalidAmpRamp {
+            max_change: stableswap::MAX_AMP_CHANGE,
+            min_ramp_time: stableswap::MIN_RAMP_TIME,
+        });
+    }
 
-    let usdt_transfer_msg = transfer_usdt(&deps, info.sender.to_string(), usdt_amount)?;
-    let orai_transfer_msg = transfer_orai(info.sender.to_string(), orai_amount)?;
+    AMP_CONFIG.save(deps.storage, &AmpConfig {
+        init_amp: amp,
+        init_amp_time: now,
+        next_amp,
+        next_amp_time,
+    })?;
 
     Ok(Response::new()
-        .add_message(burn_msg)
-        .add_message(orai_transfer_msg)
-        .add_message(usdt_transfer_msg)
-        .add_attribute("action", "remove_liquidity")
-        .add_attribute("lpt_amount", lpt_amount.to_string())
-        .add_attribute("receive_usdt", usdt_amount.to_string())
-        .add_attribute("receive_orai", orai_amount.to_string()))
+        .add_attribute("action", "ramp_amp")
+        .add_attribute("init_amp", amp.to_string())
+        .add_attribute("next_amp", next_amp.to_string())
+        .add_attribute("next_amp_time", next_amp_time.to_string()))
 }
 
-pub fn swap(
+pub fn stop_ramp_amp(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    denom: String,
-    amount: Uint128,
-) -> Result<Response, StdError> {
-    let contract_info = INFO.load(deps.storage)?;
+) -> Result<Response, ContractError> {
+    let amp_config = load_stable_amp(&deps, &info)?;
+
+    let now = env.block.time.seconds();
+    let amp = current_amp(&amp_config, now);
+    AMP_CONFIG.save(deps.storage, &AmpConfig {
+        init_amp: amp,
+        init_amp_time: now,
+        next_amp: amp,
+        next_amp_time: now,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "stop_ramp_amp")
+        .add_attribute("amp", amp.to_string()))
+}
 
-    if amount.is_zero() {
-        return Err(StdError::generic_err("swap: Amount cannot be zero"));
+// Rejects messages that land after the deadline the user signed them with
+pub fn assert_deadline(env: &Env, deadline: Option<Timestamp>) -> Result<(), ContractError> {
+    if let Some(deadline) = deadline {
+        if env.block.time > deadline {
+            return Err(ContractError::Expired { deadline, block_time: env.block.time });
+        }
     }
+    Ok(())
+}
 
-    let mut pool = POOL.load(deps.storage)?;
-    if pool.orai_reserve.is_zero() || pool.usdt_reserve.is_zero() {
-        return Err(StdError::generic_err("swap: Pool has no liquidity"));
+fn receive_lp_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    sender: Addr,
+    receiver: Addr,
+    lpt_amount: Uint128,
+    mode: WithdrawMode,
+) -> Result<Response, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+    if info.sender.as_str() != contract_info.lpt_contract {
+        return Err(ContractError::Unauthorized {});
     }
+    // the LP tokens are already held by the pair, burn them from its own balance
+    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract_info.lpt_contract.to_string(),
+        msg: to_json_binary(&Cw20ExecuteMsg::Burn { amount: lpt_amount })?,
+        funds: vec![],
+    });
+    withdraw_liquidity(deps, env, contract_info, sender, receiver, lpt_amount, burn_msg, mode)
+}
 
-    let mut response = Response::new().add_attribute("action", "swap").add_attribute("amount", amount.to_string());
+pub fn query_cw20_token_allowance(
+    querier: &QuerierWrapper,
+    owner: String,
+    spender: String,
+    token_contract: &str,
+) -> StdResult<Uint128> {
+    let response: AllowanceResponse = querier.query(&cosmwasm_std::QueryRequest::Wasm(cosmwasm_std::WasmQuery::Smart {
+        contract_addr: token_contract.to_string(),
+        msg: to_json_binary(&cw20::Cw20QueryMsg::Allowance { owner, spender })?,
+    }))?;
+    Ok(response.allowance)
+}
 
-    if denom.as_str() == DENOM_ORAI {
-        let received_orai = info
-            .funds
-            .iter()
-            .find(|coin| coin.denom == DENOM_ORAI)
-            .map(|coin| coin.amount)
-            .unwrap_or_default();
-        if received_orai < amount {
-            return Err(StdError::generic_err(format!(
-                "swap: Insufficient ORAI received. Expected: {}, Received: {}",
-                amount, received_orai
-            )));
-        }
-
-        let swap_amount = calculate_swap_amount(&pool, &denom, amount)?;
-        if swap_amount > pool.usdt_reserve {
-            return Err(StdError::generic_err(format!(
-                "swap: Insufficient USDT liquidity. Required: {}, Available: {}",
-                swap_amount, pool.usdt_reserve
-            )));
-        }
-
-        pool.orai_reserve += amount;
-        pool.usdt_reserve -= swap_amount;
-        POOL.save(deps.storage, &pool)?;
-
-        let transfer_msg = transfer_usdt(&deps, info.sender.to_string(), swap_amount)?;
-        response = response.add_message(transfer_msg).add_attribute("denom", DENOM_ORAI);
-    } else if denom.as_str() == DENOM_USDT {
-        let approved_usdt = query_cw20_token_allowance(
-            &deps,
-            info.sender.to_string(),
-            env.contract.address.to_string(),
-            &contract_info.usdt_contract.to_string(),
-        )?;
-        if approved_usdt < amount {
-            return Err(StdError::generic_err(format!(
-                "swap: Insufficient USDT allowance. Required: {}, Avaiaclable: {}",
-                amount, approved_usdt
-            )));
-        }
-        let swap_amount = calculate_swap_amount(&pool, &denom, amount)?;
-        if swap_amount > pool.orai_reserve {
-            return Err(StdError::generic_err(format!(
-                "swap: Insufficient ORAI liquidity. Required: {}, Available: {}",
-                swap_amount, pool.orai_reserve
-            )));
-        }
-
-        let transfer_from_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_info.usdt_contract.to_string(),
-            msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
-                owner: info.sender.to_string(),
-                recipient: env.contract.address.to_string(),
-                amount,
-            })?,
-            funds: vec![],
-        });
+// Position of `asset_info` in the pair, used to index `LiquidityPool.reserves`
+pub fn asset_index(
+    contract_info: &ContractInfo,
+    asset_info: &AssetInfo,
+) -> Result<usize, ContractError> {
+    contract_info
+        .asset_infos
+        .iter()
+        .position(|pair_info| pair_info == asset_info)
+        .ok_or_else(|| ContractError::UnsupportedToken { asset: asset_info.to_string() })
+}
 
-        pool.usdt_reserve += amount;
-        pool.orai_reserve -= swap_amount;
-        POOL.save(deps.storage, &pool)?;
+// Invariant of the pair with its parameters at the current block
+pub enum Curve {
+    ConstantProduct,
+    Stable { amp: u64 },
+    // the weights are those of LiquidityPool
+    Weighted,
+}
 
-        let transfer_msg = transfer_orai(info.sender.to_string(), swap_amount)?;
-        response = response.add_message(transfer_from_msg).add_message(transfer_msg).add_attribute("denom", DENOM_USDT);
-    } else {
-        return Err(StdError::generic_err("swap: Invalid token denom"));
+pub fn load_curve(
+    storage: &dyn Storage,
+    env: &Env,
+    contract_info: &ContractInfo,
+) -> StdResult<Curve> {
+    match contract_info.pair_type {
+        PairType::Xyk {} => Ok(Curve::ConstantProduct),
+        PairType::Stable {} => {
+            let amp_config = AMP_CONFIG.load(storage)?;
+            Ok(Curve::Stable { amp: current_amp(&amp_config, env.block.time.seconds()) })
+        }
+        PairType::Weighted { .. } => Ok(Curve::Weighted),
     }
-
-    Ok(response)
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(
-    deps: Deps,
-    _env: Env,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::QueryContractInfo {} => to_json_binary(&query_contract_info(deps)?),
-        QueryMsg::QueryPoolInfo {} => to_json_binary(&query_liquidity_pool_info(deps)?),
-        QueryMsg::QueryLptBalance { user } => to_json_binary(&query_lpt_balance(deps, user)?),
-        QueryMsg::QueryUSDTAllowance { user } => to_json_binary(&query_usdt_allowance_amount(deps, user)?),
+pub fn calculate_swap_amount(
+    curve: &Curve,
+    pool: &LiquidityPool,
+    offer_index: usize,
+    amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    if offer_index > 1 {
+        return Err(ContractError::InvalidTokenPair {});
+    }
+    let (offer_reserve, ask_reserve) = (pool.reserves[offer_index], pool.reserves[1 - offer_index]);
+    match curve {
+        Curve::ConstantProduct => math::swap_output(offer_reserve, ask_reserve, amount, fee_bps),
+        Curve::Stable { amp } => stableswap::swap_output(*amp, offer_reserve, ask_reserve, amount, fee_bps),
+        Curve::Weighted => {
+            let (offer_weight, ask_weight) = (pool.weights[offer_index], pool.weights[1 - offer_index]);
+            weighted::swap_output(offer_reserve, offer_weight, ask_reserve, ask_weight, amount, fee_bps)
+        }
     }
 }
 
-pub fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
-    let ct_info = INFO.load(deps.storage)?;
+// Offer needed for an output of `ask_amount`, the inverse of calculate_swap_amount
+pub fn calculate_offer_amount(
+    curve: &Curve,
+    pool: &LiquidityPool,
+    offer_index: usize,
+    ask_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let (offer_reserve, ask_reserve) = (pool.reserves[offer_index], pool.reserves[1 - offer_index]);
+    match curve {
+        Curve::ConstantProduct => math::swap_input(offer_reserve, ask_reserve, ask_amount, fee_bps),
+        Curve::Stable { amp } => stableswap::swap_input(*amp, offer_reserve, ask_reserve, ask_amount, fee_bps),
+        Curve::Weighted => {
+            let (offer_weight, ask_weight) = (pool.weights[offer_index], pool.weights[1 - offer_index]);
+            weighted::swap_input(offer_reserve, offer_weight, ask_reserve, ask_weight, ask_amount, fee_bps)
+        }
+    }
+}
+
+// Shortfall of `return_amount` against the spot price output of the same (post fee) offer. The
+// spot price of a stable pair is taken to be its peg, 1:1.
+pub fn calculate_spread_amount(
+    curve: &Curve,
+    pool: &LiquidityPool,
+    offer_index: usize,
+    amount: Uint128,
+    return_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let (offer_reserve, ask_reserve) = (pool.reserves[offer_index], pool.reserves[1 - offer_index]);
+    let spot_return = match curve {
+        Curve::ConstantProduct => math::spot_output(offer_reserve, ask_reserve, amount, fee_bps)?,
+        Curve::Stable { amp } => stableswap::spot_output(*amp, offer_reserve, ask_reserve, amount, fee_bps)?,
+        Curve::Weighted => {
+            let (offer_weight, ask_weight) = (pool.weights[offer_index], pool.weights[1 - offer_index]);
+            weighted::spot_output(offer_reserve, offer_weight, ask_reserve, ask_weight, amount, fee_bps)?
+        }
+    };
+    Ok(spot_return.saturating_sub(return_amount))
+}
+
+// Fee charged on `offer_amount`, in the offer asset, and the protocol part of it. The protocol
+// part is rounded down so the LPs never get less than their share.
+pub fn calculate_commission(
+    contract_info: &ContractInfo,
+    offer_amount: Uint128,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let total_fee_bps = contract_info.total_fee_bps();
+    let commission_amount = offer_amount.checked_sub(math::deduct_fee(offer_amount, total_fee_bps)?)?;
+    if contract_info.protocol_fee_bps == 0 {
+        return Ok((commission_amount, Uint128::zero()));
+    }
+    let protocol_fee_amount = math::mul_div_floor(
+        commission_amount,
+        Uint128::from(contract_info.protocol_fee_bps),
+        Uint128::from(total_fee_bps),
+    )?;
+    Ok((commission_amount, protocol_fee_amount))
+}
+
+// Sends the protocol part of a swap fee to the fee collector
+fn protocol_fee_msg(
+    contract_info: &ContractInfo,
+    offer_asset_info: &AssetInfo,
+    protocol_fee_amount: Uint128,
+) -> Result<Option<CosmosMsg>, ContractError> {
+    match &contract_info.fee_collector {
+        Some(fee_collector) if !protocol_fee_amount.is_zero() => {
+            let fee_asset = Asset { info: offer_asset_info.clone(), amount: protocol_fee_amount };
+            Ok(Some(transfer_token(&fee_asset, fee_collector.to_string())?))
+        }
+        _ => Ok(None),
+    }
+}
+
+// belief_price is the price the user expects to pay, in offer units per ask unit. When given,
+// max_spread bounds the shortfall against offer_amount / belief_price, otherwise against the
+// spot price of the pool.
+pub fn assert_max_spread(
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+) -> Result<(), ContractError> {
+    let max_spread = match max_spread {
+        Some(max_spread) => max_spread,
+        None => return Ok(()),
+    };
+    if max_spread > Decimal::one() {
+        return Err(ContractError::InvalidMaxSpread { max_spread });
+    }
+
+    let (expected_return, shortfall) = match belief_price {
+        Some(belief_price) => {
+            if belief_price.is_zero() {
+                return Err(ContractError::InvalidBeliefPrice {});
+            }
+            let expected_return = math::div_by_decimal(offer_amount, belief_price)?;
+            (expected_return, expected_return.saturating_sub(return_amount))
+        }
+        None => (return_amount.checked_add(spread_amount)?, spread_amount),
+    };
+
+    if expected_return.is_zero() {
+        return Ok(());
+    }
+    let spread = Decimal::from_ratio(shortfall, expected_return);
+    if spread > max_spread {
+        return Err(ContractError::MaxSpreadAssertion { max_spread, spread });
+    }
+    Ok(())
+}
+
+pub fn transfer_token(
+    asset: &Asset,
+    recipient: String,
+) -> Result<CosmosMsg, ContractError> {
+    if asset.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    Ok(asset.transfer_msg(recipient)?)
+}
+
+// Amount of `asset_info` the sender put in for this call: native coins attached to the message,
+// cw20 tokens sent beforehand through the `ProvideLiquidity` receive hook.
+pub fn provided_amount(
+    deps: &DepsMut,
+    info: &MessageInfo,
+    asset_info: &AssetInfo,
+) -> StdResult<Uint128> {
+    match asset_info {
+        AssetInfo::NativeToken { .. } => Ok(native_received(info, asset_info)),
+        AssetInfo::Token { contract_addr } => Ok(DEPOSITS
+            .may_load(deps.storage, (&info.sender, contract_addr.as_str()))?
+            .unwrap_or_default()),
+    }
+}
+
+// Escrows cw20 tokens until the sender's next AddLiquidity, see Cw20HookMsg::ProvideLiquidity
+pub fn deposit_token(
+    deps: DepsMut,
+    sender: Addr,
+    asset: Asset,
+) -> Result<Response, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+    asset_index(&contract_info, &asset.info)?;
+
+    if let AssetInfo::Token { contract_addr } = &asset.info {
+        DEPOSITS.update(deps.storage, (&sender, contract_addr.as_str()), |deposit| -> StdResult<_> {
+            Ok(deposit.unwrap_or_default() + asset.amount)
+        })?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "deposit")
+        .add_attribute("sender", sender)
+        .add_attribute("asset", asset.to_string()))
+}
+
+pub fn withdraw_deposits(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let contract_info = INFO.load(deps.storage)?;
+
+    let mut response = Response::new().add_attribute("action", "withdraw_deposits");
+    for asset_info in contract_info.asset_infos.iter() {
+        if let AssetInfo::Token { contract_addr } = asset_info {
+            let key = (&info.sender, contract_addr.as_str());
+            if let Some(amount) = DEPOSITS.may_load(deps.storage, key)? {
+                DEPOSITS.remove(deps.storage, key);
+                let asset = Asset { info: asset_info.clone(), amount };
+                response = response
+                    .add_message(transfer_token(&asset, info.sender.to_string())?)
+                    .add_attribute("asset", asset.to_string());
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+// Fails when the deposit ratio differs from the pool ratio by more than `slippage_tolerance`,
+// i.e. when the pool moved too far since the user priced the deposit.
+pub fn assert_slippage_tolerance(
+    slippage_tolerance: Option<Decimal>,
+    deposits: &[Uint128; 2],
+    reserves: &[Uint128; 2],
+) -> Result<(), ContractError> {
+    let slippage_tolerance = match slippage_tolerance {
+        Some(slippage_tolerance) => slippage_tolerance,
+        None => return Ok(()),
+    };
+    if slippage_tolerance > Decimal::one() {
+        return Err(ContractError::InvalidSlippageTolerance { slippage_tolerance });
+    }
+
+    let one_minus_slippage_tolerance = Decimal256::one() - Decimal256::from(slippage_tolerance);
+    let deposit_ratio = math::ratio(deposits[0], deposits[1])?;
+    let pool_ratio = math::ratio(reserves[0], reserves[1])?;
+
+    // the ratio may move either way, compare both directions
+    if deposit_ratio.checked_mul(one_minus_slippage_tolerance)? > pool_ratio
+        || math::ratio(deposits[1], deposits[0])?.checked_mul(one_minus_slippage_tolerance)? > math::ratio(reserves[1], reserves[0])?
+    {
+        return Err(ContractError::MaxSlippageAssertion {
+            slippage_tolerance,
+            deposit_ratio,
+            pool_ratio,
+        });
+    }
+    Ok(())
+}
+
+pub fn add_liquidity(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    assets: [Asset; 2],
+    slippage_tolerance: Option<Decimal>,
+    min_lp_out: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+    assert_expected_funds(&contract_info.asset_infos, &info)?;
+
+    // amounts in pool order, whatever order the assets were given in
+    let mut amounts = [Uint128::zero(); 2];
+    for asset in assets.iter() {
+        amounts[asset_index(&contract_info, &asset.info)?] = asset.amount;
+    }
+    if amounts[0].is_zero() || amounts[1].is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    let sender = info.sender.clone();
+    let mut pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &contract_info)?;
+    oracle::accumulate_prices(deps.storage, &env, &curve, &mut pool)?;
+
+    let (to_use, lpt_mint, lpt_locked) = if pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        let shares = match curve {
+            Curve::ConstantProduct => math::initial_shares(amounts[0], amounts[1])?,
+            Curve::Stable { amp } => stableswap::initial_shares(amp, &amounts)?,
+            Curve::Weighted => weighted::initial_shares(&amounts, &pool.weights)?,
+        };
+        if shares <= MINIMUM_LIQUIDITY {
+            return Err(ContractError::MinimumLiquidityNotReached { minimum: MINIMUM_LIQUIDITY, shares });
+        }
+        (amounts, shares - MINIMUM_LIQUIDITY, MINIMUM_LIQUIDITY)
+    } else {
+        assert_slippage_tolerance(slippage_tolerance, &amounts, &pool.reserves)?;
+
+        // use all of the scarcer leg and the matching amount of the other one, rounded up
+        let needed_0 = math::mul_div_ceil(amounts[1], pool.reserves[0], pool.reserves[1])?;
+        let to_use = if needed_0 <= amounts[0] {
+            [needed_0, amounts[1]]
+        } else {
+            [amounts[0], math::mul_div_ceil(amounts[0], pool.reserves[1], pool.reserves[0])?]
+        };
+
+        let lpt_mint = math::deposit_shares(&to_use, &pool.reserves, pool.total_shares)?;
+        (to_use, lpt_mint, Uint128::zero())
+    };
+
+    if let Some(min_lp_out) = min_lp_out {
+        if lpt_mint < min_lp_out {
+            return Err(ContractError::MinLpNotReached { min_lp_out, lpt_mint });
+        }
+    }
+
+    let mut provided = [Uint128::zero(); 2];
+    for (i, asset_info) in contract_info.asset_infos.iter().enumerate() {
+        provided[i] = provided_amount(&deps, &info, asset_info)?;
+        if provided[i] < amounts[i] {
+            return Err(ContractError::InsufficientFunds {
+                asset: asset_info.to_string(),
+                expected: amounts[i],
+                received: provided[i],
+            });
+        }
+    }
+
+    pool.reserves[0] = pool.reserves[0].checked_add(to_use[0])?;
+    pool.reserves[1] = pool.reserves[1].checked_add(to_use[1])?;
+    pool.total_shares = pool.total_shares.checked_add(lpt_mint)?.checked_add(lpt_locked)?;
+
+    let mint_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract_info.lpt_contract.to_string(),
+        msg: to_json_binary(&Cw20ExecuteMsg::Mint {
+            recipient: sender.to_string(),
+            amount: lpt_mint,
+        })?,
+        funds: vec![],
+    });
+
+    let mut response = Response::new().add_message(mint_msg);
+    if !lpt_locked.is_zero() {
+        let lock_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_info.lpt_contract.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Mint {
+                recipient: env.contract.address.to_string(),
+                amount: lpt_locked,
+            })?,
+            funds: vec![],
+        });
+        response = response
+            .add_message(lock_msg)
+            .add_attribute("lpt_locked", lpt_locked.to_string());
+    }
+
+    response = response
+        .add_attribute("action", "add_liquidity")
+        .add_attribute("assets", format!("{}, {}", Asset { info: contract_info.asset_infos[0].clone(), amount: to_use[0] }, Asset { info: contract_info.asset_infos[1].clone(), amount: to_use[1] }))
+        .add_attribute("lpt_mint", lpt_mint.to_string());
+
+    // only to_use stays in the pool: the rest of the attached coins and of the cw20 deposits,
+    // which are used up by this call, goes back to the sender
+    for (i, asset_info) in contract_info.asset_infos.iter().enumerate() {
+        if let AssetInfo::Token { contract_addr } = asset_info {
+            DEPOSITS.remove(deps.storage, (&sender, contract_addr.as_str()));
+        }
+        let unused = provided[i].checked_sub(to_use[i])?;
+        if !unused.is_zero() {
+            let refund_msg = transfer_token(&Asset { info: asset_info.clone(), amount: unused }, sender.to_string())?;
+            response = response.add_message(refund_msg);
+        }
+    }
+
+    POOL.save(deps.storage, &pool)?;
+
+    Ok(response)
+}
+
+// How the liquidity removed from the pool is paid out
+pub enum WithdrawMode {
+    // both assets pro rata, each at least its `min_assets` amount
+    Balanced { min_assets: Option<[Asset; 2]> },
+    // the other leg is swapped into `ask_asset_info` within the same call
+    SingleSided { ask_asset_info: AssetInfo, min_output: Option<Uint128> },
+}
+
+pub fn remove_liquidity(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    lpt_amount: Uint128,
+    mode: WithdrawMode,
+    to: Option<String>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let contract_info = INFO.load(deps.storage)?;
+    let receiver = recipient(deps.api, to, &info.sender)?;
+
+    if lpt_amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    // the LP token is the only record of ownership, BurnFrom would fail anyway, checked here for
+    // a clearer error
+    let lpt_balance = AssetInfo::Token { contract_addr: contract_info.lpt_contract.clone() }.query_balance(&deps.querier, info.sender.to_string())?;
+    if lpt_balance < lpt_amount {
+        return Err(ContractError::InsufficientLpBalance {
+            required: lpt_amount,
+            available: lpt_balance,
+        });
+    }
+
+    let approved_lpt = query_cw20_token_allowance(
+        &deps.querier,
+        info.sender.to_string(),
+        env.contract.address.to_string(),
+        &contract_info.lpt_contract,
+    )?;
+    if approved_lpt < lpt_amount {
+        return Err(ContractError::InsufficientAllowance {
+            token: contract_info.lpt_contract,
+            required: lpt_amount,
+            available: approved_lpt,
+        });
+    }
+
+    let burn_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract_info.lpt_contract.to_string(),
+        msg: to_json_binary(&Cw20ExecuteMsg::BurnFrom {
+            owner: info.sender.to_string(),
+            amount: lpt_amount,
+        })?,
+        funds: vec![],
+    });
+
+    withdraw_liquidity(deps, env, contract_info, info.sender, receiver, lpt_amount, burn_msg, mode)
+}
+
+// Pays out the share of both reserves backing `sender`'s `lpt_amount` to `receiver`, `burn_msg`
+// destroys the LP tokens, and the withdrawal with them if `sender` does not hold them
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_liquidity(
+    deps: DepsMut,
+    env: Env,
+    contract_info: ContractInfo,
+    sender: Addr,
+    receiver: Addr,
+    lpt_amount: Uint128,
+    burn_msg: CosmosMsg,
+    mode: WithdrawMode,
+) -> Result<Response, ContractError> {
+    if lpt_amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    let mut pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &contract_info)?;
+    oracle::accumulate_prices(deps.storage, &env, &curve, &mut pool)?;
+    if pool.total_shares.is_zero() {
+        return Err(ContractError::PoolEmpty {});
+    }
+
+    let mut amounts = [
+        math::mul_div_floor(lpt_amount, pool.reserves[0], pool.total_shares)?,
+        math::mul_div_floor(lpt_amount, pool.reserves[1], pool.total_shares)?,
+    ];
+
+    for (i, asset_info) in contract_info.asset_infos.iter().enumerate() {
+        if pool.reserves[i] < amounts[i] {
+            return Err(ContractError::InsufficientLiquidity {
+                asset: asset_info.to_string(),
+                required: amounts[i],
+                available: pool.reserves[i],
+            });
+        }
+    }
+
+    pool.reserves[0] = pool.reserves[0].checked_sub(amounts[0])?;
+    pool.reserves[1] = pool.reserves[1].checked_sub(amounts[1])?;
+    pool.total_shares = pool.total_shares.checked_sub(lpt_amount)?;
+
+    let mut response = Response::new().add_message(burn_msg);
+
+    match mode {
+        WithdrawMode::Balanced { min_assets } => {
+            if let Some(min_assets) = min_assets {
+                for min_asset in min_assets.iter() {
+                    let amount = amounts[asset_index(&contract_info, &min_asset.info)?];
+                    if amount < min_asset.amount {
+                        return Err(ContractError::MinAssetNotReached {
+                            asset: min_asset.info.to_string(),
+                            min_amount: min_asset.amount,
+                            amount,
+                        });
+                    }
+                }
+            }
+            response = response.add_attribute("action", "remove_liquidity");
+        }
+        WithdrawMode::SingleSided { ask_asset_info, min_output } => {
+            let ask_index = asset_index(&contract_info, &ask_asset_info)?;
+            let offer_index = 1 - ask_index;
+
+            // swap the other leg against the pool as it is after the withdrawal
+            if !amounts[offer_index].is_zero() && !pool.reserves[ask_index].is_zero() {
+                let swap_amount = calculate_swap_amount(&curve, &pool, offer_index, amounts[offer_index], contract_info.total_fee_bps())?;
+                let (_, protocol_fee_amount) = calculate_commission(&contract_info, amounts[offer_index])?;
+                if let Some(fee_msg) = protocol_fee_msg(&contract_info, &contract_info.asset_infos[offer_index], protocol_fee_amount)? {
+                    response = response.add_message(fee_msg);
+                }
+                pool.reserves[offer_index] = pool.reserves[offer_index].checked_add(amounts[offer_index].checked_sub(protocol_fee_amount)?)?;
+                pool.reserves[ask_index] = pool.reserves[ask_index].checked_sub(swap_amount)?;
+                amounts[ask_index] = amounts[ask_index].checked_add(swap_amount)?;
+                amounts[offer_index] = Uint128::zero();
+            }
+
+            if let Some(min_output) = min_output {
+                if amounts[ask_index] < min_output {
+                    return Err(ContractError::MinOutputNotReached { min_output, return_amount: amounts[ask_index] });
+                }
+            }
+            response = response.add_attribute("action", "withdraw_single_sided");
+        }
+    }
+
+    POOL.save(deps.storage, &pool)?;
+
+    let refund_assets = [
+        Asset { info: contract_info.asset_infos[0].clone(), amount: amounts[0] },
+        Asset { info: contract_info.asset_infos[1].clone(), amount: amounts[1] },
+    ];
+
+    for asset in refund_assets.iter().filter(|asset| !asset.amount.is_zero()) {
+        response = response.add_message(transfer_token(asset, receiver.to_string())?);
+    }
+
+    Ok(response
+        .add_attribute("sender", sender)
+        .add_attribute("receiver", receiver)
+        .add_attribute("lpt_amount", lpt_amount.to_string())
+        .add_attribute("refund_assets", format!("{}, {}", refund_assets[0], refund_assets[1])))
+}
+
+// `receiver` gets the output, the `to` of the message or else the sender, which for the receive
+// hook is the cw20 sender and not info.sender
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    receiver: Addr,
+    offer_asset: Asset,
+    min_output: Option<Uint128>,
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+
+    if offer_asset.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    let offer_index = asset_index(&contract_info, &offer_asset.info)?;
+    let ask_index = 1 - offer_index;
+
+    let mut pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &contract_info)?;
+    oracle::accumulate_prices(deps.storage, &env, &curve, &mut pool)?;
+    if pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        return Err(ContractError::PoolEmpty {});
+    }
+
+    let mut response = Response::new()
+        .add_attribute("action", "swap")
+        .add_attribute("offer_asset", offer_asset.info.to_string())
+        .add_attribute("offer_amount", offer_asset.amount.to_string());
+
+    let mut excess_offer = Uint128::zero();
+    if offer_asset.info.is_native_token() {
+        assert_offer_funds(&offer_asset.info, &info)?;
+        let received = provided_amount(&deps, &info, &offer_asset.info)?;
+        if received < offer_asset.amount {
+            return Err(ContractError::InsufficientFunds {
+                asset: offer_asset.info.to_string(),
+                expected: offer_asset.amount,
+                received,
+            });
+        }
+        excess_offer = received - offer_asset.amount;
+    }
+
+    let fee_bps = contract_info.total_fee_bps();
+    let swap_amount = calculate_swap_amount(&curve, &pool, offer_index, offer_asset.amount, fee_bps)?;
+    if swap_amount > pool.reserves[ask_index] {
+        return Err(ContractError::InsufficientLiquidity {
+            asset: contract_info.asset_infos[ask_index].to_string(),
+            required: swap_amount,
+            available: pool.reserves[ask_index],
+        });
+    }
+
+    if let Some(min_output) = min_output {
+        if swap_amount < min_output {
+            return Err(ContractError::MinOutputNotReached { min_output, return_amount: swap_amount });
+        }
+    }
+    let spread_amount = calculate_spread_amount(&curve, &pool, offer_index, offer_asset.amount, swap_amount, fee_bps)?;
+    assert_max_spread(belief_price, max_spread, offer_asset.amount, swap_amount, spread_amount)?;
+
+    // the LP part of the commission stays in the reserves, the protocol part leaves the pool
+    let (commission_amount, protocol_fee_amount) = calculate_commission(&contract_info, offer_asset.amount)?;
+    if let Some(fee_msg) = protocol_fee_msg(&contract_info, &offer_asset.info, protocol_fee_amount)? {
+        response = response.add_message(fee_msg);
+    }
+
+    pool.reserves[offer_index] = pool.reserves[offer_index].checked_add(offer_asset.amount.checked_sub(protocol_fee_amount)?)?;
+    pool.reserves[ask_index] = pool.reserves[ask_index].checked_sub(swap_amount)?;
+    POOL.save(deps.storage, &pool)?;
+
+    let return_asset = Asset {
+        info: contract_info.asset_infos[ask_index].clone(),
+        amount: swap_amount,
+    };
+    let transfer_msg = transfer_token(&return_asset, receiver.to_string())?;
+
+    if !excess_offer.is_zero() {
+        let refund_asset = Asset { info: offer_asset.info.clone(), amount: excess_offer };
+        response = response.add_message(transfer_token(&refund_asset, info.sender.to_string())?);
+    }
+
+    Ok(response
+        .add_message(transfer_msg)
+        .add_attribute("ask_asset", return_asset.info.to_string())
+        .add_attribute("return_amount", swap_amount.to_string())
+        .add_attribute("spread_amount", spread_amount.to_string())
+        .add_attribute("commission_amount", commission_amount.to_string())
+        .add_attribute("protocol_fee_amount", protocol_fee_amount.to_string()))
+}
+
+// Pays out exactly `ask_asset` to `receiver` for the offer computed by the inverse constant
+// product, taken out of `offer_budget`, the funds or tokens the sender sent with the message.
+// Whatever the swap does not need goes back to the sender.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_exact_out(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    receiver: Addr,
+    offer_budget: Asset,
+    ask_asset: Asset,
+    max_offer: Uint128,
+) -> Result<Response, ContractError> {
+    let contract_info = INFO.load(deps.storage)?;
+
+    if ask_asset.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    let ask_index = asset_index(&contract_info, &ask_asset.info)?;
+    let offer_index = 1 - ask_index;
+    if offer_budget.info != contract_info.asset_infos[offer_index] {
+        return Err(ContractError::InvalidTokenPair {});
+    }
+
+    let mut pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &contract_info)?;
+    oracle::accumulate_prices(deps.storage, &env, &curve, &mut pool)?;
+    let simulation = reverse_simulate(&contract_info, &curve, &pool, &ask_asset)?;
+    let offer_amount = simulation.offer_amount;
+    if offer_amount > max_offer {
+        return Err(ContractError::MaxOfferExceeded { max_offer, offer_amount });
+    }
+    if offer_amount > offer_budget.amount {
+        return Err(ContractError::InsufficientFunds {
+            asset: offer_budget.info.to_string(),
+            expected: offer_amount,
+            received: offer_budget.amount,
+        });
+    }
+
+    let mut response = Response::new()
+        .add_attribute("action", "swap_exact_out")
+        .add_attribute("offer_asset", offer_budget.info.to_string())
+        .add_attribute("offer_amount", offer_amount.to_string());
+
+    let (commission_amount, protocol_fee_amount) = calculate_commission(&contract_info, offer_amount)?;
+    if let Some(fee_msg) = protocol_fee_msg(&contract_info, &offer_budget.info, protocol_fee_amount)? {
+        response = response.add_message(fee_msg);
+    }
+
+    // the offer is rounded up, any output above ask_asset it would buy stays in the pool
+    pool.reserves[offer_index] = pool.reserves[offer_index].checked_add(offer_amount.checked_sub(protocol_fee_amount)?)?;
+    pool.reserves[ask_index] = pool.reserves[ask_index].checked_sub(ask_asset.amount)?;
+    POOL.save(deps.storage, &pool)?;
+
+    response = response.add_message(transfer_token(&ask_asset, receiver.to_string())?);
+
+    let excess_offer = offer_budget.amount - offer_amount;
+    if !excess_offer.is_zero() {
+        let refund_asset = Asset { info: offer_budget.info.clone(), amount: excess_offer };
+        response = response.add_message(transfer_token(&refund_asset, sender.to_string())?);
+    }
+
+    Ok(response
+        .add_attribute("ask_asset", ask_asset.info.to_string())
+        .add_attribute("return_amount", ask_asset.amount.to_string())
+        .add_attribute("refund_amount", excess_offer.to_string())
+        .add_attribute("spread_amount", simulation.spread_amount.to_string())
+        .add_attribute("commission_amount", commission_amount.to_string())
+        .add_attribute("protocol_fee_amount", protocol_fee_amount.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(
+    deps: Deps,
+    env: Env,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::QueryContractInfo {} => to_json_binary(&query_contract_info(deps)?),
+        QueryMsg::QueryPoolInfo {} => to_json_binary(&query_liquidity_pool_info(deps)?),
+        QueryMsg::QueryLptBalance { user } => to_json_binary(&query_lpt_balance(deps, user)?),
+        QueryMsg::QueryAllowance { user } => to_json_binary(&query_allowance(deps, env, user)?),
+        QueryMsg::Simulation { offer_asset } => to_json_binary(&query_simulation(deps, env, offer_asset)?),
+        QueryMsg::ReverseSimulation { ask_asset } => to_json_binary(&query_reverse_simulation(deps, env, ask_asset)?),
+        QueryMsg::Twap { start_age, end_age } => to_json_binary(&query_twap(deps, env, start_age, end_age)?),
+        QueryMsg::Amp {} => to_json_binary(&query_amp(deps, env)?),
+        QueryMsg::SpotPrice { base_asset_info } => to_json_binary(&query_spot_price(deps, env, base_asset_info)?),
+        QueryMsg::OwnershipProposal {} => to_json_binary(&query_ownership_proposal(deps)?),
+        QueryMsg::PauseStatus {} => to_json_binary(&query_pause_status(deps)?),
+    }
+}
+
+pub fn query_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+    let contract_info = INFO.load(deps.storage)?;
+    Ok(PauseStatusResponse {
+        mode: PAUSE_MODE.may_load(deps.storage)?.unwrap_or(PauseMode::Unpaused {}),
+        guardian: contract_info.guardian.map(|addr| addr.to_string()),
+    })
+}
+
+pub fn query_ownership_proposal(deps: Deps) -> StdResult<Option<OwnershipProposalResponse>> {
+    Ok(OWNERSHIP_PROPOSAL.may_load(deps.storage)?.map(|proposal| OwnershipProposalResponse {
+        proposed_owner: proposal.owner.to_string(),
+        expires_at: proposal.expires_at,
+    }))
+}
+
+pub fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
+    let ct_info = INFO.load(deps.storage)?;
     Ok(ContractInfoResponse {
         owner: ct_info.owner.to_string(),
+        asset_infos: ct_info.asset_infos,
         lpt_contract: ct_info.lpt_contract,
-        usdt_contract: ct_info.usdt_contract,
+        lp_fee_bps: ct_info.lp_fee_bps,
+        protocol_fee_bps: ct_info.protocol_fee_bps,
+        fee_collector: ct_info.fee_collector.map(|addr| addr.to_string()),
+        pair_type: ct_info.pair_type,
+        guardian: ct_info.guardian.map(|addr| addr.to_string()),
     })
 }
 
 pub fn query_liquidity_pool_info(deps: Deps) -> StdResult<PoolInfoResponse> {
+    let ct_info = INFO.load(deps.storage)?;
     let pool = POOL.load(deps.storage)?;
+    let [info_0, info_1] = ct_info.asset_infos;
     Ok(PoolInfoResponse {
-        orai_reserve: pool.orai_reserve,
-        usdt_reserve: pool.usdt_reserve,
+        assets: [
+            Asset { info: info_0, amount: pool.reserves[0] },
+            Asset { info: info_1, amount: pool.reserves[1] },
+        ],
         total_shares: pool.total_shares,
+        weights: pool.weights,
     })
 }
 
 pub fn query_lpt_balance(deps: Deps, user: String) -> StdResult<LptBalanceResponse> {
     let user_addr = deps.api.addr_validate(&user)?;
-    let balance = LIQUIDITY_PROVIDERS
-        .load(deps.storage, &user_addr)
-        .unwrap_or(Uint128::zero());
+    let contract_info = INFO.load(deps.storage)?;
+    let lpt = AssetInfo::Token { contract_addr: contract_info.lpt_contract };
+    let balance = lpt.query_balance(&deps.querier, user_addr)?;
     Ok(LptBalanceResponse { balance })
 }
 
-pub fn query_usdt_allowance_amount(deps: Deps, user: String) -> StdResult<USDTAllowanceResponse> {
+pub fn query_allowance(deps: Deps, env: Env, user: String) -> StdResult<PoolAllowanceResponse> {
     let user_addr = deps.api.addr_validate(&user)?;
-    let usdt_allowance = USDT_ALLOWANCE.load(deps.storage, &user_addr).unwrap_or(Uint128::new(123u128));
-    Ok(USDTAllowanceResponse { usdt_amount: usdt_allowance })
+    let ct_info = INFO.load(deps.storage)?;
+    let mut allowances = vec![];
+    for asset_info in ct_info.asset_infos.iter() {
+        if let AssetInfo::Token { contract_addr } = asset_info {
+            let amount = query_cw20_token_allowance(&deps.querier, user_addr.to_string(), env.contract.address.to_string(), contract_addr)?;
+            allowances.push(Asset { info: asset_info.clone(), amount });
+        }
+    }
+    Ok(PoolAllowanceResponse { allowances })
+}
+
+// Same computation as `swap`, without the funds checks and the state update
+pub fn query_simulation(deps: Deps, env: Env, offer_asset: Asset) -> StdResult<SimulationResponse> {
+    let ct_info = INFO.load(deps.storage)?;
+    let pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &ct_info)?;
+    simulate(&ct_info, &curve, &pool, &offer_asset).map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+fn simulate(
+    contract_info: &ContractInfo,
+    curve: &Curve,
+    pool: &LiquidityPool,
+    offer_asset: &Asset,
+) -> Result<SimulationResponse, ContractError> {
+    let offer_index = asset_index(contract_info, &offer_asset.info)?;
+    if pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        return Err(ContractError::PoolEmpty {});
+    }
+    let fee_bps = contract_info.total_fee_bps();
+    let return_amount = calculate_swap_amount(curve, pool, offer_index, offer_asset.amount, fee_bps)?;
+    let spread_amount = calculate_spread_amount(curve, pool, offer_index, offer_asset.amount, return_amount, fee_bps)?;
+    let (commission_amount, _) = calculate_commission(contract_info, offer_asset.amount)?;
+    Ok(SimulationResponse { return_amount, spread_amount, commission_amount })
+}
+
+pub fn query_reverse_simulation(deps: Deps, env: Env, ask_asset: Asset) -> StdResult<ReverseSimulationResponse> {
+    let ct_info = INFO.load(deps.storage)?;
+    let pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &ct_info)?;
+    reverse_simulate(&ct_info, &curve, &pool, &ask_asset).map_err(|err| StdError::generic_err(err.to_string()))
+}
+
+// The offer is rounded up, so swapping offer_amount returns at least ask_asset.amount
+fn reverse_simulate(
+    contract_info: &ContractInfo,
+    curve: &Curve,
+    pool: &LiquidityPool,
+    ask_asset: &Asset,
+) -> Result<ReverseSimulationResponse, ContractError> {
+    let ask_index = asset_index(contract_info, &ask_asset.info)?;
+    let offer_index = 1 - ask_index;
+    if pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        return Err(ContractError::PoolEmpty {});
+    }
+    if ask_asset.amount >= pool.reserves[ask_index] {
+        return Err(ContractError::InsufficientLiquidity {
+            asset: ask_asset.info.to_string(),
+            required: ask_asset.amount,
+            available: pool.reserves[ask_index],
+        });
+    }
+    let fee_bps = contract_info.total_fee_bps();
+    let offer_amount = calculate_offer_amount(curve, pool, offer_index, ask_asset.amount, fee_bps)?;
+    let spread_amount = calculate_spread_amount(curve, pool, offer_index, offer_amount, ask_asset.amount, fee_bps)?;
+    let (commission_amount, _) = calculate_commission(contract_info, offer_amount)?;
+    Ok(ReverseSimulationResponse { offer_amount, spread_amount, commission_amount })
+}
+
+pub fn query_twap(deps: Deps, env: Env, start_age: u64, end_age: u64) -> StdResult<TwapResponse> {
+    if start_age <= end_age {
+        return Err(StdError::generic_err(ContractError::InvalidTwapPeriod { start_age, end_age }.to_string()));
+    }
+    let ct_info = INFO.load(deps.storage)?;
+    let pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &ct_info)?;
+    let now = env.block.time.seconds();
+    let (price0_average, price1_average) = oracle::average_prices(
+        deps.storage,
+        &curve,
+        &pool,
+        now.saturating_sub(start_age),
+        now.saturating_sub(end_age),
+    )
+    .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(TwapResponse { price0_average, price1_average })
+}
+
+pub fn query_amp(deps: Deps, env: Env) -> StdResult<AmpResponse> {
+    let ct_info = INFO.load(deps.storage)?;
+    if ct_info.pair_type != (PairType::Stable {}) {
+        return Err(StdError::generic_err(ContractError::NotStablePair {}.to_string()));
+    }
+    let amp_config = AMP_CONFIG.load(deps.storage)?;
+    Ok(AmpResponse {
+        amp: current_amp(&amp_config, env.block.time.seconds()),
+        init_amp: amp_config.init_amp,
+        init_amp_time: amp_config.init_amp_time,
+        next_amp: amp_config.next_amp,
+        next_amp_time: amp_config.next_amp_time,
+    })
+}
+
+pub fn query_spot_price(deps: Deps, env: Env, base_asset_info: AssetInfo) -> StdResult<SpotPriceResponse> {
+    let ct_info = INFO.load(deps.storage)?;
+    let pool = POOL.load(deps.storage)?;
+    let curve = load_curve(deps.storage, &env, &ct_info)?;
+    let price = spot_price(&ct_info, &curve, &pool, &base_asset_info).map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(SpotPriceResponse { price })
+}
+
+fn spot_price(
+    contract_info: &ContractInfo,
+    curve: &Curve,
+    pool: &LiquidityPool,
+    base_asset_info: &AssetInfo,
+) -> Result<Decimal256, ContractError> {
+    curve_spot_price(curve, pool, asset_index(contract_info, base_asset_info)?)
+}
+
+// Price of the asset at base_index in the other one of the pair. The weighted formula covers
+// constant product pairs, whose weights are equal; a stable pair is priced from the derivative
+// of its invariant.
+pub fn curve_spot_price(curve: &Curve, pool: &LiquidityPool, base_index: usize) -> Result<Decimal256, ContractError> {
+    let quote_index = 1 - base_index;
+    if pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        return Err(ContractError::PoolEmpty {});
+    }
+    let (base_reserve, quote_reserve) = (pool.reserves[base_index], pool.reserves[quote_index]);
+    match curve {
+        Curve::Stable { amp } => stableswap::spot_price(*amp, base_reserve, quote_reserve),
+        _ => weighted::spot_price(quote_reserve, pool.weights[quote_index], base_reserve, pool.weights[base_index]),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
-    // use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    // use cosmwasm_std::{Addr, coins};
-
-    // #[test]
-    // fn test_add_liquidity_initial() {
-    //     let mut deps = mock_dependencies();
-    //     let env = mock_env();
-    //     let info = mock_info("sender", &coins(100, DENOM_ORAI));
-
-    //     let instantiate_msg = InstantiateMsg {
-    //         usdt_contract: Addr::unchecked("usdt_contract"),
-    //         lpt_contract: Addr::unchecked("lpt_contract"),
-    //     };
-    //     instantiate(deps.as_mut(), env.clone(), info.clone(), instantiate_msg).unwrap();
-
-    //     let res = add_liquidity(
-    //         deps.as_mut(),
-    //         env,
-    //         info,
-    //         Uint128::new(100),
-    //         Uint128::new(300),
-    //     ).unwrap();
-
-    //     let lpt_mint_attr = res
-    //         .attributes
-    //         .iter()
-    //         .find(|attr| attr.key == "lpt_mint")
-    //         .expect("Attribute 'lpt_mint' not found");
-    //     assert_eq!(lpt_mint_attr.value, "55", "lpt_mint should be 55");
-    // }
-}
\ No newline at end of file
+    use super::*;
+    use crate::asset::UnexpectedFunds;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, BankMsg, Coin, Order, OwnedDeps};
+    use cw_storage_plus::Map;
+
+    const DENOM_A: &str = "uatom";
+    const DENOM_B: &str = "uosmo";
+    const LPT: &str = "lpt";
+
+    type TestDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    fn native(denom: &str) -> AssetInfo {
+        AssetInfo::NativeToken { denom: denom.to_string() }
+    }
+
+    fn token(contract_addr: &str) -> AssetInfo {
+        AssetInfo::Token { contract_addr: contract_addr.to_string() }
+    }
+
+    // A constant product pair with its LP token registered, as after the instantiate reply
+    fn setup(asset_infos: [AssetInfo; 2]) -> TestDeps {
+        setup_pair(asset_infos, None, None)
+    }
+
+    fn setup_pair(asset_infos: [AssetInfo; 2], pair_type: Option<PairType>, amp: Option<u64>) -> TestDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            owner: "owner".to_string(),
+            asset_infos,
+            token_code_id: 1,
+            lp_fee_bps: None,
+            protocol_fee_bps: None,
+            fee_collector: None,
+            pair_type,
+            amp,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("factory", &[]), msg).unwrap();
+        let mut contract_info = INFO.load(&deps.storage).unwrap();
+        contract_info.lpt_contract = LPT.to_string();
+        INFO.save(&mut deps.storage, &contract_info).unwrap();
+        deps
+    }
+
+    fn add_liquidity_msg(amount_a: u128, amount_b: u128) -> ExecuteMsg {
+        ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(amount_a) },
+                Asset { info: native(DENOM_B), amount: Uint128::new(amount_b) },
+            ],
+            slippage_tolerance: None,
+            min_lp_out: None,
+            deadline: None,
+        }
+    }
+
+    fn provide(deps: &mut TestDeps, sender: &str, amounts: (u128, u128), funds: &[Coin]) -> Result<Response, ContractError> {
+        execute(deps.as_mut(), mock_env(), mock_info(sender, funds), add_liquidity_msg(amounts.0, amounts.1))
+    }
+
+    fn attribute<'a>(res: &'a Response, key: &str) -> &'a str {
+        &res.attributes.iter().find(|attr| attr.key == key).unwrap_or_else(|| panic!("attribute {key} not found")).value
+    }
+
+    fn mints(res: &Response) -> Vec<(String, Uint128)> {
+        res.messages
+            .iter()
+            .filter_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == LPT => match from_json(msg).unwrap() {
+                    Cw20ExecuteMsg::Mint { recipient, amount } => Some((recipient, amount)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn cw20_transfers(res: &Response, token: &str) -> Vec<(String, Uint128)> {
+        res.messages
+            .iter()
+            .filter_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == token => match from_json(msg).unwrap() {
+                    Cw20ExecuteMsg::Transfer { recipient, amount } => Some((recipient, amount)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn cw20_deposit(deps: &mut TestDeps, token: &str, sender: &str, amount: u128) {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_json_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info(token, &[]), msg).unwrap();
+    }
+
+    fn bank_sends(res: &Response) -> Vec<(String, Vec<Coin>)> {
+        res.messages
+            .iter()
+            .filter_map(|sub_msg| match &sub_msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => Some((to_address.clone(), amount.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn swap_msg(info: AssetInfo, amount: u128) -> ExecuteMsg {
+        ExecuteMsg::Swap {
+            offer_asset: Asset { info, amount: Uint128::new(amount) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+        }
+    }
+
+    fn set_fees(deps: &mut TestDeps, lp_fee_bps: u16, protocol_fee_bps: u16, fee_collector: Option<&str>) {
+        let mut contract_info = INFO.load(&deps.storage).unwrap();
+        contract_info.lp_fee_bps = lp_fee_bps;
+        contract_info.protocol_fee_bps = protocol_fee_bps;
+        contract_info.fee_collector = fee_collector.map(Addr::unchecked);
+        INFO.save(&mut deps.storage, &contract_info).unwrap();
+    }
+
+    #[test]
+    fn test_add_liquidity_initial() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let res = provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        // sqrt(100_000 * 400_000) = 200_000 shares, MINIMUM_LIQUIDITY of them minted to the pair
+        assert_eq!(attribute(&res, "lpt_mint"), "199000");
+        assert_eq!(attribute(&res, "lpt_locked"), "1000");
+        let contract_addr = mock_env().contract.address.to_string();
+        assert_eq!(mints(&res), vec![("alice".to_string(), Uint128::new(199_000)), (contract_addr, MINIMUM_LIQUIDITY)]);
+        assert!(bank_sends(&res).is_empty());
+
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [Uint128::new(100_000), Uint128::new(400_000)]);
+        assert_eq!(pool.total_shares, Uint128::new(200_000));
+    }
+
+    #[test]
+    fn test_add_liquidity_initial_below_minimum_liquidity() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let err = provide(&mut deps, "alice", (1_000, 1_000), &[coin(1_000, DENOM_A), coin(1_000, DENOM_B)]).unwrap_err();
+        assert!(matches!(err, ContractError::MinimumLiquidityNotReached { shares, .. } if shares == Uint128::new(1_000)));
+    }
+
+    #[test]
+    fn test_add_liquidity_follow_up() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        // b is the scarcer leg: 100_000 b needs 25_000 a, min(25_000 * S / A, 100_000 * S / B) = 50_000
+        let res = provide(&mut deps, "bob", (50_000, 100_000), &[coin(50_000, DENOM_A), coin(100_000, DENOM_B)]).unwrap();
+        assert_eq!(attribute(&res, "lpt_mint"), "50000");
+        assert_eq!(mints(&res), vec![("bob".to_string(), Uint128::new(50_000))]);
+
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [Uint128::new(125_000), Uint128::new(500_000)]);
+        assert_eq!(pool.total_shares, Uint128::new(250_000));
+    }
+
+    #[test]
+    fn test_add_liquidity_rounds_in_favor_of_the_pool() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        // 3 b needs 0.75 a, rounded up to 1; shares are min(1 * 2, 3 * 0.5) = 1.5, rounded down
+        let res = provide(&mut deps, "bob", (1, 3), &[coin(1, DENOM_A), coin(3, DENOM_B)]).unwrap();
+        assert_eq!(attribute(&res, "lpt_mint"), "1");
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [Uint128::new(100_001), Uint128::new(400_003)]);
+        assert_eq!(pool.total_shares, Uint128::new(200_001));
+    }
+
+    #[test]
+    fn test_locked_shares_stay_in_the_pool() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        // every share alice holds comes back through the LP token hook
+        let withdraw = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "alice".to_string(),
+            amount: Uint128::new(199_000),
+            msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: None, to: None }).unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info(LPT, &[]), withdraw).unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![
+                ("alice".to_string(), vec![coin(99_500, DENOM_A)]),
+                ("alice".to_string(), vec![coin(398_000, DENOM_B)]),
+            ]
+        );
+
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.total_shares, MINIMUM_LIQUIDITY);
+        assert_eq!(pool.reserves, [Uint128::new(500), Uint128::new(2_000)]);
+    }
+
+    #[test]
+    fn test_unbalanced_native_deposit_refunds_the_excess_leg() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        // 100_000 b only matches 25_000 a
+        let res = provide(&mut deps, "bob", (50_000, 100_000), &[coin(50_000, DENOM_A), coin(100_000, DENOM_B)]).unwrap();
+        assert_eq!(bank_sends(&res), vec![("bob".to_string(), vec![coin(25_000, DENOM_A)])]);
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::new(125_000), Uint128::new(500_000)]);
+    }
+
+    #[test]
+    fn test_unbalanced_cw20_deposit_refunds_the_excess_leg() {
+        let mut deps = setup([native(DENOM_A), token("usdt")]);
+        cw20_deposit(&mut deps, "usdt", "alice", 400_000);
+        let assets = |amount_a: u128, amount_usdt: u128| ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(amount_a) },
+                Asset { info: token("usdt"), amount: Uint128::new(amount_usdt) },
+            ],
+            slippage_tolerance: None,
+            min_lp_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[coin(100_000, DENOM_A)]), assets(100_000, 400_000)).unwrap();
+
+        // 25_000 a only matches 100_000 of the 150_000 usdt deposited
+        cw20_deposit(&mut deps, "usdt", "bob", 150_000);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(25_000, DENOM_A)]), assets(25_000, 150_000)).unwrap();
+        assert_eq!(cw20_transfers(&res, "usdt"), vec![("bob".to_string(), Uint128::new(50_000))]);
+        assert!(bank_sends(&res).is_empty());
+        assert!(DEPOSITS.may_load(&deps.storage, (&Addr::unchecked("bob"), "usdt")).unwrap().is_none());
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::new(125_000), Uint128::new(500_000)]);
+    }
+
+    #[test]
+    fn test_over_sent_funds_are_refunded() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let res = provide(&mut deps, "alice", (100_000, 400_000), &[coin(130_000, DENOM_A), coin(400_007, DENOM_B)]).unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![
+                ("alice".to_string(), vec![coin(30_000, DENOM_A)]),
+                ("alice".to_string(), vec![coin(7, DENOM_B)]),
+            ]
+        );
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::new(100_000), Uint128::new(400_000)]);
+
+        let swap = ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(1_500, DENOM_A)]), swap).unwrap();
+        assert!(bank_sends(&res).contains(&("bob".to_string(), vec![coin(500, DENOM_A)])));
+    }
+
+    #[test]
+    fn test_foreign_denoms_are_rejected() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let err = provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B), coin(1, "uother")]).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedFunds(UnexpectedFunds { denom }) if denom == "uother"));
+
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+        // the ask denom would neither be swapped nor refunded
+        let swap = ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(1_000, DENOM_A), coin(1_000, DENOM_B)]), swap).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedFunds(UnexpectedFunds { denom }) if denom == DENOM_B));
+
+        let swap_exact_out = ExecuteMsg::SwapExactOut {
+            ask_asset: Asset { info: native(DENOM_B), amount: Uint128::new(1_000) },
+            max_offer: Uint128::new(1_000),
+            deadline: None,
+            to: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(1_000, DENOM_A), coin(1, DENOM_B)]), swap_exact_out).unwrap_err();
+        assert!(matches!(err, ContractError::UnexpectedFunds(UnexpectedFunds { denom }) if denom == DENOM_B));
+    }
+
+    // TWAP over the 100 seconds a stable pair with amp 100 spent at the given reserves, and the
+    // spot prices of the curve at those reserves
+    fn stable_twap(reserves: (u128, u128)) -> (TwapResponse, (Decimal256, Decimal256)) {
+        let mut deps = setup_pair([native(DENOM_A), native(DENOM_B)], Some(PairType::Stable {}), Some(100));
+        provide(&mut deps, "alice", reserves, &[coin(reserves.0, DENOM_A), coin(reserves.1, DENOM_B)]).unwrap();
+        let pool = POOL.load(&deps.storage).unwrap();
+        let curve = Curve::Stable { amp: 100 };
+        let spot_prices = (curve_spot_price(&curve, &pool, 0).unwrap(), curve_spot_price(&curve, &pool, 1).unwrap());
+
+        // the swap accumulates the 100 seconds spent at the deposited reserves
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let swap = ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &[coin(1_000, DENOM_A)]), swap).unwrap();
+
+        (query_twap(deps.as_ref(), env, 100, 0).unwrap(), spot_prices)
+    }
+
+    #[test]
+    fn test_twap_of_an_imbalanced_stable_pair_follows_the_curve() {
+        let (twap, spot_prices) = stable_twap((1_000_000_000_000, 2_000_000_000_000));
+        assert_eq!((twap.price0_average, twap.price1_average), spot_prices);
+        // near parity on the stable curve, far from the reserve ratio of 2
+        assert!(twap.price0_average > Decimal256::one() && twap.price0_average < Decimal256::percent(101));
+        assert!(twap.price1_average > Decimal256::percent(99) && twap.price1_average < Decimal256::one());
+    }
+
+    #[test]
+    fn test_twap_of_a_small_balanced_stable_pair_is_parity() {
+        let (twap, spot_prices) = stable_twap((500_000, 500_000));
+        assert_eq!(spot_prices, (Decimal256::one(), Decimal256::one()));
+        assert_eq!((twap.price0_average, twap.price1_average), spot_prices);
+    }
+
+    #[test]
+    fn test_twap_errors() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        for (start_age, end_age) in [(10, 10), (10, 20)] {
+            let err = query_twap(deps.as_ref(), mock_env(), start_age, end_age).unwrap_err();
+            assert!(err.to_string().contains("InvalidTwapPeriod"), "{err}");
+        }
+
+        // nothing is observed before the deposit, 100 seconds before the swap
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let swap = ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("bob", &[coin(1_000, DENOM_A)]), swap).unwrap();
+        query_twap(deps.as_ref(), env.clone(), 100, 0).unwrap();
+        let err = query_twap(deps.as_ref(), env, 101, 0).unwrap_err();
+        assert!(err.to_string().contains("ObservationTooOld"), "{err}");
+    }
+
+    #[test]
+    fn test_instantiate_sets_the_given_owner() {
+        let deps = setup([native(DENOM_A), native(DENOM_B)]);
+        assert_eq!(INFO.load(&deps.storage).unwrap().owner, Addr::unchecked("owner"));
+
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            owner: String::new(),
+            asset_infos: [native(DENOM_A), native(DENOM_B)],
+            token_code_id: 1,
+            lp_fee_bps: None,
+            protocol_fee_bps: None,
+            fee_collector: None,
+            pair_type: None,
+            amp: None,
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info("factory", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)), "{err}");
+    }
+
+    #[test]
+    fn test_remove_guardian() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let update_config = ExecuteMsg::UpdateConfig {
+            lp_fee_bps: None,
+            protocol_fee_bps: None,
+            fee_collector: None,
+            guardian: Some("guardian".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update_config).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), ExecuteMsg::RemoveGuardian {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::RemoveGuardian {}).unwrap();
+        assert_eq!(attribute(&res, "previous_guardian"), "guardian");
+        assert_eq!(INFO.load(&deps.storage).unwrap().guardian, None);
+
+        let pause = ExecuteMsg::SetPauseMode { mode: PauseMode::Emergency {} };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), pause).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    // Storage of the first deployment: an ORAI/USDT pool with its contract info under the key cw2
+    // uses now, the allowance copy and the LP balance ledger
+    fn seed_legacy_layout(storage: &mut dyn Storage) {
+        storage.set(b"contract_info", br#"{"owner":"owner","usdt_contract":"usdt","lpt_contract":"lpt"}"#);
+        storage.set(b"pool", br#"{"orai_reserve":"1000000","usdt_reserve":"4000000","total_shares":"2000000"}"#);
+        let usdt_allowance: Map<&Addr, Uint128> = Map::new("usdt_allowance");
+        usdt_allowance.save(storage, &Addr::unchecked("alice"), &Uint128::new(500)).unwrap();
+        let liquidity_providers: Map<&Addr, Uint128> = Map::new("liquidity_providers");
+        liquidity_providers.save(storage, &Addr::unchecked("alice"), &Uint128::new(1_500_000)).unwrap();
+        liquidity_providers.save(storage, &Addr::unchecked("bob"), &Uint128::new(499_000)).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        let mut deps = mock_dependencies();
+        seed_legacy_layout(&mut deps.storage);
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(attribute(&res, "from_version"), "legacy");
+
+        let contract_info = INFO.load(&deps.storage).unwrap();
+        assert_eq!(contract_info.owner, Addr::unchecked("owner"));
+        assert_eq!(contract_info.asset_infos, [native("orai"), token("usdt")]);
+        assert_eq!(contract_info.lpt_contract, LPT);
+        assert_eq!(contract_info.lp_fee_bps, DEFAULT_LP_FEE_BPS);
+        assert_eq!(contract_info.pair_type, PairType::Xyk {});
+
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [Uint128::new(1_000_000), Uint128::new(4_000_000)]);
+        assert_eq!(pool.total_shares, Uint128::new(2_000_000));
+        assert_eq!(pool.block_time_last, mock_env().block.time.seconds());
+
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!((version.contract.as_str(), version.version.as_str()), (CONTRACT_NAME, CONTRACT_VERSION));
+
+        let usdt_allowance: Map<&Addr, Uint128> = Map::new("usdt_allowance");
+        let liquidity_providers: Map<&Addr, Uint128> = Map::new("liquidity_providers");
+        assert_eq!(usdt_allowance.keys(&deps.storage, None, None, Order::Ascending).count(), 0);
+        assert_eq!(liquidity_providers.keys(&deps.storage, None, None, Order::Ascending).count(), 0);
+
+        // a second run finds the current layout and leaves it as is
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(attribute(&res, "from_version"), CONTRACT_VERSION);
+        assert_eq!(INFO.load(&deps.storage).unwrap(), contract_info);
+        assert_eq!(POOL.load(&deps.storage).unwrap(), pool);
+    }
+
+    #[test]
+    fn test_migrate_rejects_downgrades_and_other_contracts() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::MigrationDowngrade { stored, .. } if stored == "99.0.0"));
+
+        set_contract_version(&mut deps.storage, "crates.io:other", CONTRACT_VERSION).unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMigration { contract, .. } if contract == "crates.io:other"));
+    }
+
+    #[test]
+    fn test_swap_splits_the_commission() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        set_fees(&mut deps, 20, 10, Some("collector"));
+        provide(&mut deps, "alice", (1_000_000, 1_000_000), &[coin(1_000_000, DENOM_A), coin(1_000_000, DENOM_B)]).unwrap();
+
+        // 0.3% of the offer, a third of it for the protocol
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(100_000, DENOM_A)]), swap_msg(native(DENOM_A), 100_000)).unwrap();
+        assert_eq!(attribute(&res, "commission_amount"), "300");
+        assert_eq!(attribute(&res, "protocol_fee_amount"), "100");
+        let return_amount: u128 = attribute(&res, "return_amount").parse().unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![("collector".to_string(), vec![coin(100, DENOM_A)]), ("bob".to_string(), vec![coin(return_amount, DENOM_B)])]
+        );
+
+        // the lp part stays in the reserves, the protocol part does not
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [Uint128::new(1_099_900), Uint128::new(1_000_000 - return_amount)]);
+
+        // the protocol part is rounded down, here to nothing, and then no transfer is sent
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(500, DENOM_A)]), swap_msg(native(DENOM_A), 500)).unwrap();
+        assert_eq!(attribute(&res, "commission_amount"), "2");
+        assert_eq!(attribute(&res, "protocol_fee_amount"), "0");
+        assert!(bank_sends(&res).iter().all(|(to, _)| to == "bob"));
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves[0], Uint128::new(1_100_400));
+    }
+
+    #[test]
+    fn test_swap_without_protocol_fee_keeps_the_commission() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (1_000_000, 1_000_000), &[coin(1_000_000, DENOM_A), coin(1_000_000, DENOM_B)]).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(100_000, DENOM_A)]), swap_msg(native(DENOM_A), 100_000)).unwrap();
+        assert_eq!(attribute(&res, "commission_amount"), "300");
+        assert_eq!(attribute(&res, "protocol_fee_amount"), "0");
+        assert_eq!(bank_sends(&res).len(), 1);
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves[0], Uint128::new(1_100_000));
+    }
+
+    #[test]
+    fn test_assert_fees() {
+        let collector = Some(Addr::unchecked("collector"));
+        assert_fees(MAX_LP_FEE_BPS, MAX_PROTOCOL_FEE_BPS, &collector).unwrap();
+        assert_fees(0, 0, &None).unwrap();
+
+        let err = assert_fees(MAX_LP_FEE_BPS + 1, 0, &collector).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { lp_fee_bps: 101, protocol_fee_bps: 0, .. }), "{err}");
+        let err = assert_fees(0, MAX_PROTOCOL_FEE_BPS + 1, &collector).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { lp_fee_bps: 0, protocol_fee_bps: 51, .. }), "{err}");
+        let err = assert_fees(30, 1, &None).unwrap_err();
+        assert!(matches!(err, ContractError::FeeCollectorNotSet {}), "{err}");
+
+        // checked on instantiate and on every config update
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            owner: "owner".to_string(),
+            asset_infos: [native(DENOM_A), native(DENOM_B)],
+            token_code_id: 1,
+            lp_fee_bps: None,
+            protocol_fee_bps: Some(10),
+            fee_collector: None,
+            pair_type: None,
+            amp: None,
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info("factory", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::FeeCollectorNotSet {}), "{err}");
+
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let update_config = ExecuteMsg::UpdateConfig {
+            lp_fee_bps: Some(MAX_LP_FEE_BPS + 1),
+            protocol_fee_bps: None,
+            fee_collector: None,
+            guardian: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update_config).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { .. }), "{err}");
+        assert_eq!(INFO.load(&deps.storage).unwrap().lp_fee_bps, DEFAULT_LP_FEE_BPS);
+    }
+
+    fn propose(deps: &mut TestDeps, sender: &str, owner: &str, expires_in: u64) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ProposeNewOwner { owner: owner.to_string(), expires_in };
+        execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        assert_eq!(query_ownership_proposal(deps.as_ref()).unwrap(), None);
+
+        let err = propose(&mut deps, "alice", "alice", 100).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        for (owner, expires_in) in [("owner", 100), ("alice", 0), ("alice", MAX_OWNERSHIP_PROPOSAL_EXPIRY + 1)] {
+            let err = propose(&mut deps, "owner", owner, expires_in).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidOwnershipProposal { .. }), "{err}");
+        }
+
+        propose(&mut deps, "owner", "alice", 100).unwrap();
+        let expires_at = mock_env().block.time.plus_seconds(100);
+        assert_eq!(
+            query_ownership_proposal(deps.as_ref()).unwrap(),
+            Some(OwnershipProposalResponse { proposed_owner: "alice".to_string(), expires_at })
+        );
+
+        // only the proposed owner can claim, the current owner stays until then
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        assert_eq!(INFO.load(&deps.storage).unwrap().owner, Addr::unchecked("owner"));
+
+        let mut env = mock_env();
+        env.block.time = expires_at;
+        let res = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::ClaimOwnership {}).unwrap();
+        assert_eq!(attribute(&res, "previous_owner"), "owner");
+        assert_eq!(attribute(&res, "new_owner"), "alice");
+        assert_eq!(INFO.load(&deps.storage).unwrap().owner, Addr::unchecked("alice"));
+        assert_eq!(query_ownership_proposal(deps.as_ref()).unwrap(), None);
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert!(matches!(err, ContractError::OwnershipProposalNotFound {}), "{err}");
+        let err = propose(&mut deps, "owner", "bob", 100).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+    }
+
+    #[test]
+    fn test_ownership_proposal_expiry_and_drop() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        propose(&mut deps, "owner", "alice", 100).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(101);
+        let err = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert!(matches!(err, ContractError::OwnershipProposalExpired { expires_at } if expires_at == mock_env().block.time.plus_seconds(100)), "{err}");
+        assert_eq!(INFO.load(&deps.storage).unwrap().owner, Addr::unchecked("owner"));
+
+        // a new proposal replaces the expired one, and the owner can drop it
+        propose(&mut deps, "owner", "bob", 200).unwrap();
+        assert_eq!(query_ownership_proposal(deps.as_ref()).unwrap().unwrap().proposed_owner, "bob");
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::DropOwnershipProposal {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::DropOwnershipProposal {}).unwrap();
+        assert_eq!(query_ownership_proposal(deps.as_ref()).unwrap(), None);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::ClaimOwnership {}).unwrap_err();
+        assert!(matches!(err, ContractError::OwnershipProposalNotFound {}), "{err}");
+    }
+
+    #[test]
+    fn test_update_config_is_owner_only() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        let update_config = ExecuteMsg::UpdateConfig {
+            lp_fee_bps: Some(20),
+            protocol_fee_bps: Some(10),
+            fee_collector: Some("collector".to_string()),
+            guardian: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), update_config.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        assert_eq!(INFO.load(&deps.storage).unwrap().lp_fee_bps, DEFAULT_LP_FEE_BPS);
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update_config.clone()).unwrap();
+        assert_eq!(attribute(&res, "fee_collector"), "collector");
+        let contract_info = INFO.load(&deps.storage).unwrap();
+        assert_eq!((contract_info.lp_fee_bps, contract_info.protocol_fee_bps), (20, 10));
+        assert_eq!(contract_info.fee_collector, Some(Addr::unchecked("collector")));
+
+        // the claimed owner takes over the config, the previous one loses it
+        propose(&mut deps, "owner", "alice", 100).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::ClaimOwnership {}).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update_config.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}), "{err}");
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), update_config).unwrap();
+    }
+
+    // sent by the token contract, on behalf of alice
+    fn hook(msg: &Cw20HookMsg) -> ExecuteMsg {
+        ExecuteMsg::Receive(Cw20ReceiveMsg { sender: "alice".to_string(), amount: Uint128::new(1_000), msg: to_json_binary(msg).unwrap() })
+    }
+
+    fn set_pause_mode(deps: &mut TestDeps, mode: PauseMode) {
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::SetPauseMode { mode }).unwrap();
+    }
+
+    #[test]
+    fn test_pause_modes_block_their_operations() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+
+        let remove_liquidity = ExecuteMsg::RemoveLiquidity { lpt_amount: Uint128::new(1_000), min_assets: None, deadline: None, to: None };
+        let single_sided = ExecuteMsg::WithdrawSingleSided {
+            lpt_amount: Uint128::new(1_000),
+            ask_asset_info: native(DENOM_A),
+            min_output: None,
+            deadline: None,
+            to: None,
+        };
+        let swap_exact_out = ExecuteMsg::SwapExactOut {
+            ask_asset: Asset { info: native(DENOM_B), amount: Uint128::new(1_000) },
+            max_offer: Uint128::new(1_000),
+            deadline: None,
+            to: None,
+        };
+        let hook_swap = Cw20HookMsg::Swap { min_output: None, belief_price: None, max_spread: None, deadline: None, to: None };
+        let hook_swap_exact_out = Cw20HookMsg::SwapExactOut {
+            ask_asset: Asset { info: native(DENOM_B), amount: Uint128::new(100) },
+            max_offer: None,
+            deadline: None,
+            to: None,
+        };
+        let hook_withdraw = Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: None, to: None };
+        let hook_single_sided = Cw20HookMsg::WithdrawSingleSided { ask_asset_info: native(DENOM_A), min_output: None, deadline: None, to: None };
+        // sender, message, funds, and the operation it is paused with
+        let messages = [
+            ("alice", add_liquidity_msg(1_000, 4_000), vec![coin(1_000, DENOM_A), coin(4_000, DENOM_B)], "deposits"),
+            ("token", hook(&Cw20HookMsg::ProvideLiquidity {}), vec![], "deposits"),
+            ("alice", swap_msg(native(DENOM_A), 1_000), vec![coin(1_000, DENOM_A)], "swaps"),
+            ("alice", swap_exact_out, vec![coin(1_000, DENOM_A)], "swaps"),
+            ("alice", single_sided, vec![], "swaps"),
+            ("token", hook(&hook_swap), vec![], "swaps"),
+            ("token", hook(&hook_swap_exact_out), vec![], "swaps"),
+            (LPT, hook(&hook_single_sided), vec![], "swaps"),
+            ("alice", remove_liquidity, vec![], "withdrawals"),
+            ("alice", ExecuteMsg::WithdrawDeposits {}, vec![], "withdrawals"),
+            (LPT, hook(&hook_withdraw), vec![], "withdrawals"),
+        ];
+
+        let modes = [
+            (PauseMode::Unpaused {}, vec![]),
+            (PauseMode::Swaps {}, vec!["swaps"]),
+            (PauseMode::Deposits {}, vec!["deposits"]),
+            (PauseMode::AllExceptWithdrawals {}, vec!["swaps", "deposits"]),
+            (PauseMode::Emergency {}, vec!["swaps", "deposits", "withdrawals"]),
+        ];
+        for (mode, blocked) in modes {
+            set_pause_mode(&mut deps, mode.clone());
+            for (sender, msg, funds, operation) in messages.iter() {
+                // the pause check comes first, other failures of a message do not matter here
+                let paused = match execute(deps.as_mut(), mock_env(), mock_info(sender, funds), msg.clone()) {
+                    Err(ContractError::Paused { operation: paused }) => {
+                        assert_eq!(&paused, operation);
+                        true
+                    }
+                    _ => false,
+                };
+                assert_eq!(paused, blocked.contains(operation), "{msg:?} in {mode}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_withdrawals_stay_open_until_emergency() {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (100_000, 400_000), &[coin(100_000, DENOM_A), coin(400_000, DENOM_B)]).unwrap();
+        let withdraw = hook(&Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: None, to: None });
+
+        for mode in [PauseMode::Swaps {}, PauseMode::Deposits {}, PauseMode::AllExceptWithdrawals {}] {
+            set_pause_mode(&mut deps, mode.clone());
+            let res = execute(deps.as_mut(), mock_env(), mock_info(LPT, &[]), withdraw.clone()).unwrap();
+            // both legs are paid out
+            assert_eq!(bank_sends(&res).len(), 2, "{mode}");
+        }
+
+        set_pause_mode(&mut deps, PauseMode::Emergency {});
+        let shares = POOL.load(&deps.storage).unwrap().total_shares;
+        let err = execute(deps.as_mut(), mock_env(), mock_info(LPT, &[]), withdraw.clone()).unwrap_err();
+        assert!(matches!(&err, ContractError::Paused { operation } if operation == "withdrawals"), "{err}");
+        assert_eq!(POOL.load(&deps.storage).unwrap().total_shares, shares);
+
+        // the pause itself can still be lifted
+        set_pause_mode(&mut deps, PauseMode::Unpaused {});
+        execute(deps.as_mut(), mock_env(), mock_info(LPT, &[]), withdraw).unwrap();
+    }
+
+    fn balanced_pool() -> TestDeps {
+        let mut deps = setup([native(DENOM_A), native(DENOM_B)]);
+        provide(&mut deps, "alice", (1_000_000, 1_000_000), &[coin(1_000_000, DENOM_A), coin(1_000_000, DENOM_B)]).unwrap();
+        deps
+    }
+
+    #[test]
+    fn test_swap_min_output() {
+        let mut deps = balanced_pool();
+        let offer_asset = Asset { info: native(DENOM_A), amount: Uint128::new(10_000) };
+        let return_amount = query_simulation(deps.as_ref(), mock_env(), offer_asset.clone()).unwrap().return_amount;
+        let swap = |min_output: u128| ExecuteMsg::Swap {
+            offer_asset: offer_asset.clone(),
+            min_output: Some(Uint128::new(min_output)),
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap(return_amount.u128() + 1)).unwrap_err();
+        assert!(matches!(err, ContractError::MinOutputNotReached { min_output, return_amount: returned } if min_output == return_amount + Uint128::one() && returned == return_amount), "{err}");
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap(return_amount.u128())).unwrap();
+        assert_eq!(bank_sends(&res), vec![("bob".to_string(), vec![coin(return_amount.u128(), DENOM_B)])]);
+    }
+
+    #[test]
+    fn test_assert_max_spread() {
+        let one_percent = Some(Decimal::percent(1));
+
+        // against the belief price: 10_000 offered at 1 is expected to return 10_000
+        assert_max_spread(Some(Decimal::one()), one_percent, Uint128::new(10_000), Uint128::new(9_900), Uint128::zero()).unwrap();
+        let err = assert_max_spread(Some(Decimal::one()), one_percent, Uint128::new(10_000), Uint128::new(9_899), Uint128::zero()).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSpreadAssertion { spread, .. } if spread == Decimal::permille(10) + Decimal::from_ratio(1u128, 10_000u128)), "{err}");
+
+        // against the spot price, from the spread amount
+        assert_max_spread(None, one_percent, Uint128::new(1_000), Uint128::new(990), Uint128::new(10)).unwrap();
+        let err = assert_max_spread(None, one_percent, Uint128::new(1_000), Uint128::new(989), Uint128::new(11)).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSpreadAssertion { .. }), "{err}");
+
+        // no max spread, no check
+        assert_max_spread(Some(Decimal::one()), None, Uint128::new(10_000), Uint128::zero(), Uint128::zero()).unwrap();
+
+        let err = assert_max_spread(None, Some(Decimal::percent(101)), Uint128::new(1_000), Uint128::new(990), Uint128::new(10)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxSpread { .. }), "{err}");
+        let err = assert_max_spread(Some(Decimal::zero()), one_percent, Uint128::new(1_000), Uint128::new(990), Uint128::new(10)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBeliefPrice {}), "{err}");
+
+        // through a swap: 10_000 into a pool of 1_000_000 loses 0.3% to the fee and about 1% to the price impact
+        let mut deps = balanced_pool();
+        let swap = |max_spread: u64| ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(10_000) },
+            min_output: None,
+            belief_price: Some(Decimal::one()),
+            max_spread: Some(Decimal::percent(max_spread)),
+            to: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap(1)).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSpreadAssertion { .. }), "{err}");
+        execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap(2)).unwrap();
+    }
+
+    fn add_liquidity_with(amounts: (u128, u128), slippage_tolerance: Option<Decimal>, min_lp_out: Option<u128>) -> (ExecuteMsg, Vec<Coin>) {
+        let msg = ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(amounts.0) },
+                Asset { info: native(DENOM_B), amount: Uint128::new(amounts.1) },
+            ],
+            slippage_tolerance,
+            min_lp_out: min_lp_out.map(Uint128::new),
+            deadline: None,
+        };
+        (msg, vec![coin(amounts.0, DENOM_A), coin(amounts.1, DENOM_B)])
+    }
+
+    #[test]
+    fn test_add_liquidity_slippage_tolerance_and_min_lp_out() {
+        let reserves = [Uint128::new(1_000_000), Uint128::new(1_000_000)];
+        let deposits = [Uint128::new(1_000), Uint128::new(980)];
+        assert_slippage_tolerance(None, &deposits, &reserves).unwrap();
+        assert_slippage_tolerance(Some(Decimal::percent(3)), &deposits, &reserves).unwrap();
+        // a ratio of 1.0204 is more than 1% off parity, in either direction
+        let err = assert_slippage_tolerance(Some(Decimal::percent(1)), &deposits, &reserves).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSlippageAssertion { .. }), "{err}");
+        let err = assert_slippage_tolerance(Some(Decimal::percent(1)), &[deposits[1], deposits[0]], &reserves).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSlippageAssertion { .. }), "{err}");
+        let err = assert_slippage_tolerance(Some(Decimal::percent(101)), &deposits, &reserves).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSlippageTolerance { .. }), "{err}");
+
+        let mut deps = balanced_pool();
+        let (msg, funds) = add_liquidity_with((1_000, 980), Some(Decimal::percent(1)), None);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSlippageAssertion { .. }), "{err}");
+        let (msg, funds) = add_liquidity_with((1_000, 980), Some(Decimal::percent(3)), None);
+        execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), msg).unwrap();
+
+        // 10_000 of each mints 1% of the shares
+        let shares = POOL.load(&deps.storage).unwrap().total_shares;
+        let expected = Uint128::new(10_000).multiply_ratio(shares, POOL.load(&deps.storage).unwrap().reserves[0]);
+        let (msg, funds) = add_liquidity_with((10_000, 10_000), None, Some(expected.u128() + 1));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MinLpNotReached { min_lp_out, lpt_mint } if min_lp_out == expected + Uint128::one() && lpt_mint == expected), "{err}");
+        let (msg, funds) = add_liquidity_with((10_000, 10_000), None, Some(expected.u128()));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), msg).unwrap();
+        assert_eq!(mints(&res), vec![("bob".to_string(), expected)]);
+    }
+
+    fn withdraw_hook(deps: &mut TestDeps, lpt_amount: u128, msg: &Cw20HookMsg) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg { sender: "alice".to_string(), amount: Uint128::new(lpt_amount), msg: to_json_binary(msg).unwrap() });
+        execute(deps.as_mut(), mock_env(), mock_info(LPT, &[]), msg)
+    }
+
+    #[test]
+    fn test_withdraw_min_assets_and_single_sided() {
+        let mut deps = balanced_pool();
+        let min_assets = |amount_a: u128, amount_b: u128| Cw20HookMsg::WithdrawLiquidity {
+            min_assets: Some([
+                Asset { info: native(DENOM_A), amount: Uint128::new(amount_a) },
+                Asset { info: native(DENOM_B), amount: Uint128::new(amount_b) },
+            ]),
+            deadline: None,
+            to: None,
+        };
+
+        // 1% of the shares withdraws 10_000 of each
+        let err = withdraw_hook(&mut deps, 10_000, &min_assets(10_001, 0)).unwrap_err();
+        assert!(matches!(&err, ContractError::MinAssetNotReached { asset, amount, .. } if asset == DENOM_A && amount == Uint128::new(10_000)), "{err}");
+        let err = withdraw_hook(&mut deps, 10_000, &min_assets(0, 10_001)).unwrap_err();
+        assert!(matches!(&err, ContractError::MinAssetNotReached { asset, .. } if asset == DENOM_B), "{err}");
+        let res = withdraw_hook(&mut deps, 10_000, &min_assets(10_000, 10_000)).unwrap();
+        assert_eq!(bank_sends(&res), vec![("alice".to_string(), vec![coin(10_000, DENOM_A)]), ("alice".to_string(), vec![coin(10_000, DENOM_B)])]);
+
+        // single sided, the uosmo leg is swapped back into the pool for more uatom
+        let single_sided = |min_output: u128| Cw20HookMsg::WithdrawSingleSided {
+            ask_asset_info: native(DENOM_A),
+            min_output: Some(Uint128::new(min_output)),
+            deadline: None,
+            to: None,
+        };
+        let err = withdraw_hook(&mut deps, 10_000, &single_sided(20_000)).unwrap_err();
+        let ContractError::MinOutputNotReached { return_amount, .. } = err else {
+            panic!("{err}");
+        };
+        assert!(return_amount > Uint128::new(19_000) && return_amount < Uint128::new(20_000), "{return_amount}");
+
+        let reserves = POOL.load(&deps.storage).unwrap().reserves;
+        let res = withdraw_hook(&mut deps, 10_000, &single_sided(return_amount.u128())).unwrap();
+        assert_eq!(attribute(&res, "action"), "withdraw_single_sided");
+        assert_eq!(bank_sends(&res), vec![("alice".to_string(), vec![coin(return_amount.u128(), DENOM_A)])]);
+        let pool = POOL.load(&deps.storage).unwrap();
+        assert_eq!(pool.reserves, [reserves[0] - return_amount, reserves[1]]);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let mut deps = balanced_pool();
+        let now = mock_env().block.time;
+        let swap = |deadline: Timestamp| ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            deadline: Some(deadline),
+        };
+        let add_liquidity = |deadline: Timestamp| ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(1_000) },
+                Asset { info: native(DENOM_B), amount: Uint128::new(1_000) },
+            ],
+            slippage_tolerance: None,
+            min_lp_out: None,
+            deadline: Some(deadline),
+        };
+        let withdraw = |deadline: Timestamp| Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: Some(deadline), to: None };
+
+        // a deadline in the past fails, one at the current block still goes through
+        let past = now.minus_seconds(1);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(1_000, DENOM_A)]), swap(past)).unwrap_err();
+        assert!(matches!(err, ContractError::Expired { deadline, block_time } if deadline == past && block_time == now), "{err}");
+        let funds = [coin(1_000, DENOM_A), coin(1_000, DENOM_B)];
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), add_liquidity(past)).unwrap_err();
+        assert!(matches!(err, ContractError::Expired { .. }), "{err}");
+        let err = withdraw_hook(&mut deps, 1_000, &withdraw(past)).unwrap_err();
+        assert!(matches!(err, ContractError::Expired { .. }), "{err}");
+
+        execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(1_000, DENOM_A)]), swap(now)).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), add_liquidity(now)).unwrap();
+        withdraw_hook(&mut deps, 1_000, &withdraw(now.plus_seconds(60))).unwrap();
+    }
+
+    #[test]
+    fn test_simulation_and_reverse_simulation() {
+        let mut deps = balanced_pool();
+        let offer_asset = Asset { info: native(DENOM_A), amount: Uint128::new(10_000) };
+
+        // 0.3% of 10_000 as fee, then 9_970 * 1e6 / 1_009_970, the spread is the shortfall against the spot price
+        let simulation = query_simulation(deps.as_ref(), mock_env(), offer_asset.clone()).unwrap();
+        assert_eq!(
+            simulation,
+            SimulationResponse { return_amount: Uint128::new(9_871), spread_amount: Uint128::new(99), commission_amount: Uint128::new(30) }
+        );
+
+        // the offer is rounded up so that it buys at least the ask amount
+        let ask_asset = Asset { info: native(DENOM_B), amount: Uint128::new(9_871) };
+        let reverse = query_reverse_simulation(deps.as_ref(), mock_env(), ask_asset).unwrap();
+        assert!(reverse.offer_amount <= offer_asset.amount, "{}", reverse.offer_amount);
+        let round_trip = query_simulation(deps.as_ref(), mock_env(), Asset { info: native(DENOM_A), amount: reverse.offer_amount }).unwrap();
+        assert_eq!(round_trip.return_amount, Uint128::new(9_871));
+
+        // and the swap returns what was simulated
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap_msg(native(DENOM_A), 10_000)).unwrap();
+        assert_eq!(attribute(&res, "return_amount"), "9871");
+        assert_eq!(attribute(&res, "spread_amount"), "99");
+        assert_eq!(attribute(&res, "commission_amount"), "30");
+
+        let err = query_simulation(deps.as_ref(), mock_env(), Asset { info: native("uusd"), amount: Uint128::new(1_000) }).unwrap_err();
+        assert!(err.to_string().contains("uusd"), "{err}");
+        let reserve = POOL.load(&deps.storage).unwrap().reserves[1];
+        let err = query_reverse_simulation(deps.as_ref(), mock_env(), Asset { info: native(DENOM_B), amount: reserve }).unwrap_err();
+        assert!(err.to_string().contains("InsufficientLiquidity"), "{err}");
+
+        let empty = setup([native(DENOM_A), native(DENOM_B)]);
+        let err = query_simulation(empty.as_ref(), mock_env(), offer_asset).unwrap_err();
+        assert!(err.to_string().contains("PoolEmpty"), "{err}");
+        let err = query_reverse_simulation(empty.as_ref(), mock_env(), Asset { info: native(DENOM_B), amount: Uint128::new(1) }).unwrap_err();
+        assert!(err.to_string().contains("PoolEmpty"), "{err}");
+    }
+
+    #[test]
+    fn test_swap_exact_out_refunds_the_unused_offer() {
+        let mut deps = balanced_pool();
+        let ask_asset = Asset { info: native(DENOM_B), amount: Uint128::new(9_871) };
+        let offer_amount = query_reverse_simulation(deps.as_ref(), mock_env(), ask_asset.clone()).unwrap().offer_amount;
+        let swap_exact_out = |max_offer: Uint128| ExecuteMsg::SwapExactOut { ask_asset: ask_asset.clone(), max_offer, deadline: None, to: None };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(12_000, DENOM_A)]), swap_exact_out(offer_amount - Uint128::one())).unwrap_err();
+        assert!(matches!(err, ContractError::MaxOfferExceeded { max_offer, offer_amount: needed } if max_offer == offer_amount - Uint128::one() && needed == offer_amount), "{err}");
+        let funds = [coin(offer_amount.u128() - 1, DENOM_A)];
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &funds), swap_exact_out(Uint128::new(12_000))).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds { expected, .. } if expected == offer_amount), "{err}");
+
+        // exactly the ask amount out, the rest of the funds back to the sender
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(12_000, DENOM_A)]), swap_exact_out(Uint128::new(12_000))).unwrap();
+        assert_eq!(attribute(&res, "offer_amount"), offer_amount.to_string());
+        assert_eq!(
+            bank_sends(&res),
+            vec![("bob".to_string(), vec![coin(9_871, DENOM_B)]), ("bob".to_string(), vec![coin(12_000 - offer_amount.u128(), DENOM_A)])]
+        );
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::new(1_000_000) + offer_amount, Uint128::new(1_000_000 - 9_871)]);
+    }
+
+    #[test]
+    fn test_cw20_swap_exact_out_refunds_the_unused_offer() {
+        let mut deps = setup([native(DENOM_A), token("usdt")]);
+        cw20_deposit(&mut deps, "usdt", "alice", 1_000_000);
+        let add_liquidity = ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(1_000_000) },
+                Asset { info: token("usdt"), amount: Uint128::new(1_000_000) },
+            ],
+            slippage_tolerance: None,
+            min_lp_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[coin(1_000_000, DENOM_A)]), add_liquidity).unwrap();
+
+        // the tokens sent are the budget, max_offer defaults to them
+        let swap_exact_out = |max_offer: Option<u128>| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "bob".to_string(),
+                amount: Uint128::new(12_000),
+                msg: to_json_binary(&Cw20HookMsg::SwapExactOut {
+                    ask_asset: Asset { info: native(DENOM_A), amount: Uint128::new(9_871) },
+                    max_offer: max_offer.map(Uint128::new),
+                    deadline: None,
+                    to: None,
+                })
+                .unwrap(),
+            })
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("usdt", &[]), swap_exact_out(Some(9_000))).unwrap_err();
+        assert!(matches!(err, ContractError::MaxOfferExceeded { .. }), "{err}");
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("usdt", &[]), swap_exact_out(None)).unwrap();
+        let offer_amount: u128 = attribute(&res, "offer_amount").parse().unwrap();
+        assert!(offer_amount <= 10_000, "{offer_amount}");
+        assert_eq!(bank_sends(&res), vec![("bob".to_string(), vec![coin(9_871, DENOM_A)])]);
+        assert_eq!(cw20_transfers(&res, "usdt"), vec![("bob".to_string(), Uint128::new(12_000 - offer_amount))]);
+    }
+
+    #[test]
+    fn test_to_receives_the_output() {
+        let mut deps = balanced_pool();
+        let swap = |to: &str| ExecuteMsg::Swap {
+            offer_asset: Asset { info: native(DENOM_A), amount: Uint128::new(10_000) },
+            min_output: None,
+            belief_price: None,
+            max_spread: None,
+            to: Some(to.to_string()),
+            deadline: None,
+        };
+
+        // the output goes to carol, the excess funds back to bob who sent them
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(11_000, DENOM_A)]), swap("carol")).unwrap();
+        assert_eq!(bank_sends(&res), vec![("bob".to_string(), vec![coin(1_000, DENOM_A)]), ("carol".to_string(), vec![coin(9_871, DENOM_B)])]);
+
+        let withdraw = Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: None, to: Some("carol".to_string()) };
+        let res = withdraw_hook(&mut deps, 1_000, &withdraw).unwrap();
+        assert!(bank_sends(&res).iter().all(|(to, _)| to == "carol"));
+        assert_eq!(attribute(&res, "sender"), "alice");
+        assert_eq!(attribute(&res, "receiver"), "carol");
+
+        // an invalid address fails instead of sending the output nowhere
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(10_000, DENOM_A)]), swap("Carol")).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::GenericErr { .. })), "{err}");
+        let withdraw = Cw20HookMsg::WithdrawLiquidity { min_assets: None, deadline: None, to: Some(String::new()) };
+        let err = withdraw_hook(&mut deps, 1_000, &withdraw).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)), "{err}");
+    }
+
+    #[test]
+    fn test_cw20_deposit_escrow_refund_and_consumption() {
+        let mut deps = setup([native(DENOM_A), token("usdt")]);
+        let deposit = |deps: &TestDeps, sender: &str| DEPOSITS.may_load(&deps.storage, (&Addr::unchecked(sender), "usdt")).unwrap();
+        let add_liquidity = |amount_a: u128, amount_usdt: u128| ExecuteMsg::AddLiquidity {
+            assets: [
+                Asset { info: native(DENOM_A), amount: Uint128::new(amount_a) },
+                Asset { info: token("usdt"), amount: Uint128::new(amount_usdt) },
+            ],
+            slippage_tolerance: None,
+            min_lp_out: None,
+            deadline: None,
+        };
+
+        // escrowed per sender and added up, nothing enters the pool yet
+        cw20_deposit(&mut deps, "usdt", "alice", 300_000);
+        cw20_deposit(&mut deps, "usdt", "alice", 100_000);
+        assert_eq!(deposit(&deps, "alice"), Some(Uint128::new(400_000)));
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::zero(); 2]);
+        let foreign = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "alice".to_string(),
+            amount: Uint128::new(1_000),
+            msg: to_json_binary(&Cw20HookMsg::ProvideLiquidity {}).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other_token", &[]), foreign).unwrap_err();
+        assert!(err.to_string().contains("other_token"), "{err}");
+
+        // the AddLiquidity of another sender cannot use them
+        let err = execute(deps.as_mut(), mock_env(), mock_info("bob", &[coin(100_000, DENOM_A)]), add_liquidity(100_000, 400_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds { received, .. } if received.is_zero()), "{err}");
+
+        // refunded in full by WithdrawDeposits, and only once
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::WithdrawDeposits {}).unwrap();
+        assert_eq!(cw20_transfers(&res, "usdt"), vec![("alice".to_string(), Uint128::new(400_000))]);
+        assert_eq!(deposit(&deps, "alice"), None);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::WithdrawDeposits {}).unwrap();
+        assert!(res.messages.is_empty());
+
+        // consumed by the next AddLiquidity: the cw20 leg comes from the deposit, the rest is sent back
+        cw20_deposit(&mut deps, "usdt", "alice", 450_000);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[coin(100_000, DENOM_A)]), add_liquidity(100_000, 500_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds { received, .. } if received == Uint128::new(450_000)), "{err}");
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[coin(100_000, DENOM_A)]), add_liquidity(100_000, 400_000)).unwrap();
+        assert_eq!(cw20_transfers(&res, "usdt"), vec![("alice".to_string(), Uint128::new(50_000))]);
+        assert_eq!(deposit(&deps, "alice"), None);
+        assert_eq!(POOL.load(&deps.storage).unwrap().reserves, [Uint128::new(100_000), Uint128::new(400_000)]);
+    }
+}
@@ -1,27 +1,156 @@
 // use core::error;
 
-use cosmwasm_std::StdError;
+use cosmwasm_std::{CheckedFromRatioError, ConversionOverflowError, Decimal, Decimal256, DivideByZeroError, OverflowError, StdError, Timestamp, Uint128};
+use cw_utils::{ParseReplyError, PaymentError};
 use thiserror::Error;
 
+use crate::asset::UnexpectedFunds;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
+    #[error("{0}")]
+    ConversionOverflow(#[from] ConversionOverflowError),
+
+    #[error("{0}")]
+    CheckedFromRatio(#[from] CheckedFromRatioError),
+
     #[error("Unauthorized")]
     Unauthorized {},
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 
-    #[error("InvalidTokenAmount")]
-    InvalidTokenAmount {}, 
+    #[error("ZeroAmount: amount cannot be zero")]
+    ZeroAmount {},
 
-    #[error("InvalidReserve")]
-    InvalidReserve {},
+    #[error("PoolEmpty: pool has no liquidity")]
+    PoolEmpty {},
 
-    #[error("InsufficientLiquidity")]
-    InsufficientLiquidity {}, 
+    #[error("InsufficientLiquidity: {required}{asset} required, {available} available")]
+    InsufficientLiquidity { asset: String, required: Uint128, available: Uint128 },
 
     #[error("InvalidTokenPair")]
     InvalidTokenPair {},
+
+    #[error("UnsupportedToken: {asset} is not an asset of this pair")]
+    UnsupportedToken { asset: String },
+
+    #[error("InsufficientFunds: expected {expected}{asset}, received {received}")]
+    InsufficientFunds { asset: String, expected: Uint128, received: Uint128 },
+
+    #[error("{0}")]
+    UnexpectedFunds(#[from] UnexpectedFunds),
+
+    #[error("InsufficientAllowance: {required} of {token} required, {available} approved")]
+    InsufficientAllowance { token: String, required: Uint128, available: Uint128 },
+
+    #[error("InsufficientLpBalance: {required} LP tokens required, {available} available")]
+    InsufficientLpBalance { required: Uint128, available: Uint128 },
+
+    #[error("Cw20OfferNotReceived: {asset} must be offered through the cw20 Receive hook")]
+    Cw20OfferNotReceived { asset: String },
+
+    #[error("LpTokenAlreadyRegistered: {lpt_contract}")]
+    LpTokenAlreadyRegistered { lpt_contract: String },
+
+    #[error("UnknownReplyId: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("MinOutputNotReached: returned {return_amount}, expected at least {min_output}")]
+    MinOutputNotReached { min_output: Uint128, return_amount: Uint128 },
+
+    #[error("MaxOfferExceeded: swap needs an offer of {offer_amount}, above max offer {max_offer}")]
+    MaxOfferExceeded { max_offer: Uint128, offer_amount: Uint128 },
+
+    #[error("MaxSpreadAssertion: spread {spread} exceeds max spread {max_spread}")]
+    MaxSpreadAssertion { max_spread: Decimal, spread: Decimal },
+
+    #[error("InvalidMaxSpread: max spread {max_spread} is above 1")]
+    InvalidMaxSpread { max_spread: Decimal },
+
+    #[error("InvalidBeliefPrice: belief price cannot be zero")]
+    InvalidBeliefPrice {},
+
+    #[error("MaxSlippageAssertion: deposit ratio {deposit_ratio} is off pool ratio {pool_ratio} by more than {slippage_tolerance}")]
+    MaxSlippageAssertion { slippage_tolerance: Decimal, deposit_ratio: Decimal256, pool_ratio: Decimal256 },
+
+    #[error("InvalidSlippageTolerance: slippage tolerance {slippage_tolerance} is above 1")]
+    InvalidSlippageTolerance { slippage_tolerance: Decimal },
+
+    #[error("MinAssetNotReached: withdrew {amount}{asset}, expected at least {min_amount}")]
+    MinAssetNotReached { asset: String, min_amount: Uint128, amount: Uint128 },
+
+    #[error("MinimumLiquidityNotReached: first deposit is worth {shares} shares, must be above {minimum}")]
+    MinimumLiquidityNotReached { minimum: Uint128, shares: Uint128 },
+
+    #[error("MinLpNotReached: minted {lpt_mint} LP tokens, expected at least {min_lp_out}")]
+    MinLpNotReached { min_lp_out: Uint128, lpt_mint: Uint128 },
+
+    #[error("InvalidFee: lp fee {lp_fee_bps} bps and protocol fee {protocol_fee_bps} bps must be at most {max_lp_fee_bps} and {max_protocol_fee_bps} bps")]
+    InvalidFee { lp_fee_bps: u16, protocol_fee_bps: u16, max_lp_fee_bps: u16, max_protocol_fee_bps: u16 },
+
+    #[error("FeeCollectorNotSet: a protocol fee needs a fee collector")]
+    FeeCollectorNotSet {},
+
+    #[error("InvalidAmp: amp {amp} must be between {min_amp} and {max_amp}")]
+    InvalidAmp { amp: u64, min_amp: u64, max_amp: u64 },
+
+    #[error("AmpMissing: stable pairs need an initial amp")]
+    AmpMissing {},
+
+    #[error("InvalidAmpRamp: amp can change by a factor of at most {max_change}, over at least {min_ramp_time} seconds")]
+    InvalidAmpRamp { max_change: u64, min_ramp_time: u64 },
+
+    #[error("NotStablePair: only stable pairs have an amp")]
+    NotStablePair {},
+
+    #[error("InvalidWeights: weights must each be between 2% and 98% and add up to 1")]
+    InvalidWeights {},
+
+    #[error("MaxTradeRatioExceeded: weighted pairs take at most 50% of the offer reserve and pay out at most 30% of the ask reserve per swap")]
+    MaxTradeRatioExceeded {},
+
+    #[error("InvalidTwapPeriod: start age {start_age} must be above end age {end_age}")]
+    InvalidTwapPeriod { start_age: u64, end_age: u64 },
+
+    #[error("ObservationTooOld: no price observation at {timestamp}, the oldest is at {oldest}")]
+    ObservationTooOld { timestamp: u64, oldest: u64 },
+
+    #[error("Expired: deadline {deadline} passed, block time is {block_time}")]
+    Expired { deadline: Timestamp, block_time: Timestamp },
+
+    #[error("InvalidOwnershipProposal: new owner must differ from the owner and expires_in must be between 1 and {max_expires_in} seconds")]
+    InvalidOwnershipProposal { max_expires_in: u64 },
+
+    #[error("OwnershipProposalNotFound: there is no pending ownership proposal")]
+    OwnershipProposalNotFound {},
+
+    #[error("OwnershipProposalExpired: the proposal expired at {expires_at}")]
+    OwnershipProposalExpired { expires_at: Timestamp },
+
+    #[error("Paused: {operation} are paused")]
+    Paused { operation: String },
+
+    #[error("{0}")]
+    Semver(#[from] semver::Error),
+
+    #[error("InvalidMigration: cannot migrate {contract} to {expected}")]
+    InvalidMigration { contract: String, expected: String },
+
+    #[error("MigrationDowngrade: stored version {stored} is newer than {version}")]
+    MigrationDowngrade { stored: String, version: String },
 }
@@ -1,6 +1,12 @@
+pub mod asset;
 pub mod contract;
 pub mod error;
+pub mod math;
+pub mod migration;
 pub mod msg;
+pub mod oracle;
+pub mod stableswap;
 pub mod state;
+pub mod weighted;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,112 @@
+// AMM arithmetic on Uint256/Decimal256 with checked operations. Whenever a result has to be
+// rounded, it is rounded in favor of the pool: amounts paid out and shares minted round down,
+// amounts taken in round up.
+use cosmwasm_std::{Decimal, Decimal256, Uint128, Uint256};
+
+use crate::error::ContractError;
+
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+// a * b / c, rounded down
+pub fn mul_div_floor(a: Uint128, b: Uint128, c: Uint128) -> Result<Uint128, ContractError> {
+    let result = Uint256::from(a)
+        .checked_mul(Uint256::from(b))?
+        .checked_div(Uint256::from(c))?;
+    Ok(Uint128::try_from(result)?)
+}
+
+// a * b / c, rounded up
+pub fn mul_div_ceil(a: Uint128, b: Uint128, c: Uint128) -> Result<Uint128, ContractError> {
+    let numerator = Uint256::from(a).checked_mul(Uint256::from(b))?;
+    let c = Uint256::from(c);
+    let mut result = numerator.checked_div(c)?;
+    if !numerator.checked_rem(c)?.is_zero() {
+        result = result.checked_add(Uint256::one())?;
+    }
+    Ok(Uint128::try_from(result)?)
+}
+
+// amount / price, rounded down
+pub fn div_by_decimal(amount: Uint128, price: Decimal) -> Result<Uint128, ContractError> {
+    mul_div_floor(amount, Decimal::one().atomics(), price.atomics())
+}
+
+pub fn ratio(numerator: Uint128, denominator: Uint128) -> Result<Decimal256, ContractError> {
+    Ok(Decimal256::checked_from_ratio(numerator, denominator)?)
+}
+
+// Largest integer whose square is at most `value`
+pub fn isqrt(value: Uint256) -> Uint256 {
+    if value.is_zero() {
+        return value;
+    }
+    // Newton's method from an initial guess above the root, decreasing monotonically
+    let two = Uint256::from(2u8);
+    let mut x = value;
+    let mut y = (x + Uint256::one()) / two;
+    while y < x {
+        x = y;
+        y = (x + value / x) / two;
+    }
+    x
+}
+
+// Offer amount left once the swap fee is taken, rounded down so the fee is never undercharged
+pub fn deduct_fee(amount: Uint128, fee_bps: u16) -> Result<Uint128, ContractError> {
+    mul_div_floor(amount, Uint128::from(BPS_DENOMINATOR - fee_bps as u128), Uint128::from(BPS_DENOMINATOR))
+}
+
+// Constant product output for `offer_amount` after fees, rounded down
+pub fn swap_output(
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = deduct_fee(offer_amount, fee_bps)?;
+    let denominator = Uint256::from(offer_reserve).checked_add(Uint256::from(offer_net))?;
+    let result = Uint256::from(offer_net)
+        .checked_mul(Uint256::from(ask_reserve))?
+        .checked_div(denominator)?;
+    Ok(Uint128::try_from(result)?)
+}
+
+// Smallest offer whose constant product output after fees is at least `ask_amount`, the
+// inverse of swap_output. `ask_amount` must be below `ask_reserve`.
+pub fn swap_input(
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    ask_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = mul_div_ceil(offer_reserve, ask_amount, ask_reserve.checked_sub(ask_amount)?)?;
+    mul_div_ceil(offer_net, Uint128::from(BPS_DENOMINATOR), Uint128::from(BPS_DENOMINATOR - fee_bps as u128))
+}
+
+// Output of `offer_amount` after fees at the current spot price, i.e. without price impact
+pub fn spot_output(
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = deduct_fee(offer_amount, fee_bps)?;
+    mul_div_floor(offer_net, ask_reserve, offer_reserve)
+}
+
+// LP tokens for the first deposit: sqrt(amount_0 * amount_1), rounded down
+pub fn initial_shares(amount_0: Uint128, amount_1: Uint128) -> Result<Uint128, ContractError> {
+    let product = Uint256::from(amount_0).checked_mul(Uint256::from(amount_1))?;
+    Ok(Uint128::try_from(isqrt(product))?)
+}
+
+// LP tokens for a later deposit: min(a * S / A, b * S / B), so neither leg is overpaid
+pub fn deposit_shares(
+    deposits: &[Uint128; 2],
+    reserves: &[Uint128; 2],
+    total_shares: Uint128,
+) -> Result<Uint128, ContractError> {
+    let shares_0 = mul_div_floor(deposits[0], total_shares, reserves[0])?;
+    let shares_1 = mul_div_floor(deposits[1], total_shares, reserves[1])?;
+    Ok(shares_0.min(shares_1))
+}
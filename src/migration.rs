@@ -0,0 +1,101 @@
+// Storage migrations run by the migrate entry point, each one from the layout of an older version
+// to the current one
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Env, Order, Storage, Uint128, Uint256};
+use cw_storage_plus::{Item, Map};
+
+use crate::asset::AssetInfo;
+use crate::contract::DEFAULT_LP_FEE_BPS;
+use crate::error::ContractError;
+use crate::oracle;
+use crate::state::{ContractInfo, LiquidityPool, Observation, PairType, INFO, POOL};
+
+// Native denom of the first deployment, which only paired ORAI with a USDT cw20
+const LEGACY_DENOM_ORAI: &str = "orai";
+
+// Layouts of the first deployment, the contract info was stored under the key cw2 now uses
+#[cw_serde]
+struct LegacyLiquidityPool {
+    orai_reserve: Uint128,
+    usdt_reserve: Uint128,
+    total_shares: Uint128,
+}
+
+#[cw_serde]
+struct LegacyContractInfo {
+    owner: Addr,
+    usdt_contract: String,
+    lpt_contract: String,
+}
+
+const LEGACY_POOL: Item<LegacyLiquidityPool> = Item::new("pool");
+const LEGACY_INFO: Item<LegacyContractInfo> = Item::new("contract_info");
+
+// Allowances were queried from the cw20 contract since the pair went generic, this copy is stale
+const LEGACY_USDT_ALLOWANCE: Map<&Addr, Uint128> = Map::new("usdt_allowance");
+
+// LP balances were mirrored here until the LP token became the only record, transfers of the
+// token left it out of date
+const LEGACY_LIQUIDITY_PROVIDERS: Map<&Addr, Uint128> = Map::new("liquidity_providers");
+
+// Whether the contract is the first deployment, which predates cw2 versioning
+pub fn is_legacy(storage: &dyn Storage) -> Result<bool, ContractError> {
+    Ok(INFO.may_load(storage)?.is_none() && LEGACY_INFO.may_load(storage)?.is_some())
+}
+
+// Moves the ORAI/USDT pool of the first deployment to the generic layout: ORAI is asset 0 and USDT
+// asset 1, with the 0.3% fee it always charged, and the price accumulators start from this block
+pub fn migrate_legacy_pool(storage: &mut dyn Storage, env: &Env) -> Result<(), ContractError> {
+    let legacy_info = LEGACY_INFO.load(storage)?;
+    let legacy_pool = LEGACY_POOL.load(storage)?;
+    LEGACY_INFO.remove(storage);
+
+    INFO.save(storage, &ContractInfo {
+        owner: legacy_info.owner,
+        asset_infos: [
+            AssetInfo::NativeToken { denom: LEGACY_DENOM_ORAI.to_string() },
+            AssetInfo::Token { contract_addr: legacy_info.usdt_contract },
+        ],
+        lpt_contract: legacy_info.lpt_contract,
+        lp_fee_bps: DEFAULT_LP_FEE_BPS,
+        protocol_fee_bps: 0,
+        fee_collector: None,
+        pair_type: PairType::Xyk {},
+        guardian: None,
+    })?;
+
+    let pool = LiquidityPool {
+        reserves: [legacy_pool.orai_reserve, legacy_pool.usdt_reserve],
+        total_shares: legacy_pool.total_shares,
+        price0_cumulative_last: Uint256::zero(),
+        price1_cumulative_last: Uint256::zero(),
+        block_time_last: env.block.time.seconds(),
+        weights: [Decimal::percent(50), Decimal::percent(50)],
+    };
+    POOL.save(storage, &pool)?;
+    oracle::push_observation(storage, &Observation {
+        timestamp: pool.block_time_last,
+        price0_cumulative: pool.price0_cumulative_last,
+        price1_cumulative: pool.price1_cumulative_last,
+    })?;
+
+    let stale = LEGACY_USDT_ALLOWANCE
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<Result<Vec<_>, _>>()?;
+    for user in stale.iter() {
+        LEGACY_USDT_ALLOWANCE.remove(storage, user);
+    }
+
+    Ok(())
+}
+
+// Deletes the LP balance ledger, a no-op once it is gone
+pub fn remove_liquidity_providers(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let stale = LEGACY_LIQUIDITY_PROVIDERS
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<Result<Vec<_>, _>>()?;
+    for user in stale.iter() {
+        LEGACY_LIQUIDITY_PROVIDERS.remove(storage, user);
+    }
+    Ok(())
+}
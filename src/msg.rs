@@ -1,18 +1,144 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Decimal, Decimal256, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
+
+use crate::asset::{Asset, AssetInfo};
+use crate::state::{PairType, PauseMode};
 
 #[cw_serde]
 pub struct InstantiateMsg {
-    // pub owner: String, 
-    pub usdt_contract: String, 
-    pub lpt_contract: String, 
+    // administers the pair: fees, pause, amp and ownership transfers
+    pub owner: String,
+    pub asset_infos: [AssetInfo; 2],
+    // code id of the cw20-base contract instantiated as the LP token
+    pub token_code_id: u64,
+    // swap fee left in the pool, 30 (0.3%) when not set
+    pub lp_fee_bps: Option<u16>,
+    // swap fee sent to fee_collector, none when not set
+    pub protocol_fee_bps: Option<u16>,
+    pub fee_collector: Option<String>,
+    // constant product when not set
+    pub pair_type: Option<PairType>,
+    // initial amplification, required for stable pairs
+    pub amp: Option<u64>,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
-    AddLiquidity { orai_amount: Uint128, usdt_amount: Uint128 },
-    RemoveLiquidity { lpt_amount: Uint128 },
-    Swap { denom: String, amount: Uint128 },
+    AddLiquidity {
+        assets: [Asset; 2],
+        // max relative difference between the deposit ratio and the pool ratio
+        slippage_tolerance: Option<Decimal>,
+        // reverts when fewer LP tokens would be minted
+        min_lp_out: Option<Uint128>,
+        // reverts when executed after this block time
+        deadline: Option<Timestamp>,
+    },
+    RemoveLiquidity {
+        lpt_amount: Uint128,
+        // reverts when either payout is below its minimum
+        min_assets: Option<[Asset; 2]>,
+        deadline: Option<Timestamp>,
+        // receives the withdrawn assets instead of the sender
+        to: Option<String>,
+    },
+    // withdraws both legs and swaps the other one into ask_asset_info
+    WithdrawSingleSided {
+        lpt_amount: Uint128,
+        ask_asset_info: AssetInfo,
+        min_output: Option<Uint128>,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
+    // native offers only, cw20 offers go through Receive
+    Swap {
+        offer_asset: Asset,
+        // reverts when the swap returns less than this
+        min_output: Option<Uint128>,
+        // expected price in offer units per ask unit, max_spread is measured against it
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        deadline: Option<Timestamp>,
+        // receives the swap output instead of the sender, refunds still go to the sender
+        to: Option<String>,
+    },
+    // native offers only: pays out exactly ask_asset for the smallest offer, the rest of the
+    // attached funds is refunded
+    SwapExactOut {
+        ask_asset: Asset,
+        // reverts when the required offer is above this
+        max_offer: Uint128,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
+    // returns cw20 tokens sent with ProvideLiquidity that no AddLiquidity has used
+    WithdrawDeposits {},
+    // owner only, fields not given are kept
+    UpdateConfig {
+        lp_fee_bps: Option<u16>,
+        protocol_fee_bps: Option<u16>,
+        fee_collector: Option<String>,
+        guardian: Option<String>,
+    },
+    // owner only, leaves the pair without a guardian
+    RemoveGuardian {},
+    // owner or guardian only
+    SetPauseMode { mode: PauseMode },
+    // owner only, owner can claim the ownership within expires_in seconds
+    ProposeNewOwner {
+        owner: String,
+        expires_in: u64,
+    },
+    // proposed owner only, before the proposal expires
+    ClaimOwnership {},
+    // owner only
+    DropOwnershipProposal {},
+    // owner only, stable pairs: moves amp linearly to next_amp, reached at next_amp_time
+    RampAmp {
+        next_amp: u64,
+        next_amp_time: u64,
+    },
+    // owner only, stable pairs: keeps amp at its current value
+    StopRampAmp {},
+    Receive(Cw20ReceiveMsg),
+}
+
+// Messages embedded in a cw20 Send to the pool
+#[cw_serde]
+pub enum Cw20HookMsg {
+    Swap {
+        min_output: Option<Uint128>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
+    // the tokens sent are the most that can be offered, the unused part is sent back
+    SwapExactOut {
+        ask_asset: Asset,
+        max_offer: Option<Uint128>,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
+    // first step of a deposit with a cw20 leg, as a Send cannot carry the other asset: credits the
+    // tokens to the sender, then the sender's next AddLiquidity takes its cw20 leg from them and
+    // sends back what it does not use. WithdrawDeposits returns them if that never comes.
+    ProvideLiquidity {},
+    // sent with the LP token, burns it and pays out the share of the reserves
+    WithdrawLiquidity {
+        min_assets: Option<[Asset; 2]>,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
+    WithdrawSingleSided {
+        ask_asset_info: AssetInfo,
+        min_output: Option<Uint128>,
+        deadline: Option<Timestamp>,
+        to: Option<String>,
+    },
 }
 
 #[cw_serde]
@@ -27,34 +153,116 @@ pub enum QueryMsg {
     #[returns(LptBalanceResponse)]
     QueryLptBalance { user: String },
 
-    #[returns(USDTAllowanceResponse)]
-    QueryUSDTAllowance { user: String },
+    #[returns(AllowanceResponse)]
+    QueryAllowance { user: String },
+
+    // outcome of swapping offer_asset at the current reserves
+    #[returns(SimulationResponse)]
+    Simulation { offer_asset: Asset },
+
+    // offer needed to receive exactly ask_asset at the current reserves
+    #[returns(ReverseSimulationResponse)]
+    ReverseSimulation { ask_asset: Asset },
+
+    // time weighted average prices between start_age and end_age seconds ago
+    #[returns(TwapResponse)]
+    Twap { start_age: u64, end_age: u64 },
+
+    // stable pairs only
+    #[returns(AmpResponse)]
+    Amp {},
+
+    // marginal price of base_asset_info in the other asset of the pair, before fees
+    #[returns(SpotPriceResponse)]
+    SpotPrice { base_asset_info: AssetInfo },
+
+    // pending ownership transfer, null when there is none
+    #[returns(Option<OwnershipProposalResponse>)]
+    OwnershipProposal {},
+
+    #[returns(PauseStatusResponse)]
+    PauseStatus {},
 }
 
 
 #[cw_serde]
 
 pub struct PoolInfoResponse {
-    pub orai_reserve: Uint128, 
-    pub usdt_reserve: Uint128, 
-    pub total_shares: Uint128, 
+    pub assets: [Asset; 2],
+    pub total_shares: Uint128,
+    pub weights: [Decimal; 2],
 }
 
 
 #[cw_serde]
 pub struct LptBalanceResponse {
-    pub balance: Uint128, 
+    pub balance: Uint128,
 }
 
 
 #[cw_serde]
 pub struct ContractInfoResponse {
-    pub owner: String, 
-    pub lpt_contract: String, 
-    pub usdt_contract: String, 
+    pub owner: String,
+    pub asset_infos: [AssetInfo; 2],
+    pub lpt_contract: String,
+    pub lp_fee_bps: u16,
+    pub protocol_fee_bps: u16,
+    pub fee_collector: Option<String>,
+    pub pair_type: PairType,
+    pub guardian: Option<String>,
 }
 
+// cw20 allowances the user has granted to the pool, one entry per cw20 asset of the pair
 #[cw_serde]
-pub struct USDTAllowanceResponse {
-    pub usdt_amount: Uint128, 
-}
\ No newline at end of file
+pub struct AllowanceResponse {
+    pub allowances: Vec<Asset>,
+}
+
+// Amounts are in the ask asset, except commission_amount which is taken from the offer
+#[cw_serde]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct ReverseSimulationResponse {
+    pub offer_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+}
+
+// price0 is asset 0 priced in asset 1, price1 the other way around
+#[cw_serde]
+pub struct TwapResponse {
+    pub price0_average: Decimal256,
+    pub price1_average: Decimal256,
+}
+
+#[cw_serde]
+pub struct AmpResponse {
+    // amplification at the current block
+    pub amp: u64,
+    pub init_amp: u64,
+    pub init_amp_time: u64,
+    pub next_amp: u64,
+    pub next_amp_time: u64,
+}
+
+#[cw_serde]
+pub struct SpotPriceResponse {
+    pub price: Decimal256,
+}
+
+#[cw_serde]
+pub struct OwnershipProposalResponse {
+    pub proposed_owner: String,
+    pub expires_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct PauseStatusResponse {
+    pub mode: PauseMode,
+    pub guardian: Option<String>,
+}
@@ -0,0 +1,243 @@
+// Uniswap V2 style price oracle. The pool accumulates price * seconds for both assets, and every
+// block that changes the reserves leaves an observation of the accumulators in a ring buffer, so
+// the average price between two past times can be read back. Accumulators are in Decimal256
+// atomics and wrap around on overflow, only their differences are meaningful.
+use cosmwasm_std::{Decimal256, Env, Storage, Uint256};
+
+use crate::contract::{curve_spot_price, Curve};
+use crate::error::ContractError;
+use crate::state::{LiquidityPool, Observation, OBSERVATIONS, OBSERVATION_COUNT};
+
+// Number of observations kept, the oldest one is overwritten first
+pub const OBSERVATION_BUFFER_SIZE: u64 = 1_000;
+
+// Price of asset 0 in asset 1 and of asset 1 in asset 0 on the curve of the pair
+fn spot_prices(curve: &Curve, pool: &LiquidityPool) -> Result<(Decimal256, Decimal256), ContractError> {
+    Ok((curve_spot_price(curve, pool, 0)?, curve_spot_price(curve, pool, 1)?))
+}
+
+// Accumulators of `pool` extrapolated to `timestamp`, with the current reserves
+fn accumulated(curve: &Curve, pool: &LiquidityPool, timestamp: u64) -> Result<(Uint256, Uint256), ContractError> {
+    let elapsed = timestamp.saturating_sub(pool.block_time_last);
+    if elapsed == 0 || pool.reserves[0].is_zero() || pool.reserves[1].is_zero() {
+        return Ok((pool.price0_cumulative_last, pool.price1_cumulative_last));
+    }
+    let (price0, price1) = spot_prices(curve, pool)?;
+    let elapsed = Uint256::from(elapsed);
+    Ok((
+        pool.price0_cumulative_last.wrapping_add(price0.atomics().checked_mul(elapsed)?),
+        pool.price1_cumulative_last.wrapping_add(price1.atomics().checked_mul(elapsed)?),
+    ))
+}
+
+pub fn push_observation(storage: &mut dyn Storage, observation: &Observation) -> Result<(), ContractError> {
+    let count = OBSERVATION_COUNT.may_load(storage)?.unwrap_or_default();
+    OBSERVATIONS.save(storage, count % OBSERVATION_BUFFER_SIZE, observation)?;
+    OBSERVATION_COUNT.save(storage, &(count + 1))?;
+    Ok(())
+}
+
+// Brings the accumulators up to the current block with the reserves as they were before this
+// block, and records them. Must run before any change to the reserves.
+pub fn accumulate_prices(
+    storage: &mut dyn Storage,
+    env: &Env,
+    curve: &Curve,
+    pool: &mut LiquidityPool,
+) -> Result<(), ContractError> {
+    let now = env.block.time.seconds();
+    if now <= pool.block_time_last {
+        return Ok(());
+    }
+    let (price0_cumulative, price1_cumulative) = accumulated(curve, pool, now)?;
+    pool.price0_cumulative_last = price0_cumulative;
+    pool.price1_cumulative_last = price1_cumulative;
+    pool.block_time_last = now;
+    push_observation(storage, &Observation { timestamp: now, price0_cumulative, price1_cumulative })
+}
+
+// Accumulators at `timestamp`: interpolated between the observations around it, or extrapolated
+// from the pool when it is after the last reserve change. The price is constant between two
+// observations, so the interpolation is exact.
+pub fn cumulative_prices_at(
+    storage: &dyn Storage,
+    curve: &Curve,
+    pool: &LiquidityPool,
+    timestamp: u64,
+) -> Result<(Uint256, Uint256), ContractError> {
+    if timestamp >= pool.block_time_last {
+        return accumulated(curve, pool, timestamp);
+    }
+
+    let count = OBSERVATION_COUNT.may_load(storage)?.unwrap_or_default();
+    let len = count.min(OBSERVATION_BUFFER_SIZE);
+    let oldest_slot = count - len;
+    let load = |i: u64| OBSERVATIONS.load(storage, (oldest_slot + i) % OBSERVATION_BUFFER_SIZE);
+
+    let oldest = load(0)?;
+    if timestamp < oldest.timestamp {
+        return Err(ContractError::ObservationTooOld { timestamp, oldest: oldest.timestamp });
+    }
+
+    // last observation at or before timestamp; the newest one is at block_time_last, after it
+    let (mut low, mut high) = (0, len - 1);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if load(mid)?.timestamp <= timestamp {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let before = load(low)?;
+    let after = load(high)?;
+
+    let period = Uint256::from(after.timestamp - before.timestamp);
+    let elapsed = Uint256::from(timestamp - before.timestamp);
+    let interpolate = |start: Uint256, end: Uint256| -> Result<Uint256, ContractError> {
+        let rate = end.wrapping_sub(start).checked_div(period)?;
+        Ok(start.wrapping_add(rate.checked_mul(elapsed)?))
+    };
+    Ok((
+        interpolate(before.price0_cumulative, after.price0_cumulative)?,
+        interpolate(before.price1_cumulative, after.price1_cumulative)?,
+    ))
+}
+
+// Average prices between `start` and `end` (timestamps, start before end)
+pub fn average_prices(
+    storage: &dyn Storage,
+    curve: &Curve,
+    pool: &LiquidityPool,
+    start: u64,
+    end: u64,
+) -> Result<(Decimal256, Decimal256), ContractError> {
+    let (start_0, start_1) = cumulative_prices_at(storage, curve, pool, start)?;
+    let (end_0, end_1) = cumulative_prices_at(storage, curve, pool, end)?;
+    let period = Uint256::from(end - start);
+    Ok((
+        Decimal256::new(end_0.wrapping_sub(start_0).checked_div(period)?),
+        Decimal256::new(end_1.wrapping_sub(start_1).checked_div(period)?),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+    use cosmwasm_std::{Decimal, Uint128};
+
+    fn pool(reserves: (u128, u128), block_time_last: u64) -> LiquidityPool {
+        LiquidityPool {
+            reserves: [Uint128::new(reserves.0), Uint128::new(reserves.1)],
+            total_shares: Uint128::zero(),
+            price0_cumulative_last: Uint256::zero(),
+            price1_cumulative_last: Uint256::zero(),
+            block_time_last,
+            weights: [Decimal::percent(50); 2],
+        }
+    }
+
+    fn env_at(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = cosmwasm_std::Timestamp::from_seconds(seconds);
+        env
+    }
+
+    // price * seconds in Decimal256 atomics
+    fn cumulative(price: Decimal256, seconds: u64) -> Uint256 {
+        price.atomics() * Uint256::from(seconds)
+    }
+
+    #[test]
+    fn accumulate_prices_sums_constant_product_prices() {
+        let mut storage = MockStorage::new();
+        let curve = Curve::ConstantProduct;
+        let mut pool = pool((1_000, 4_000), 100);
+
+        // 10 seconds at 4 asset 1 per asset 0
+        accumulate_prices(&mut storage, &env_at(110), &curve, &mut pool).unwrap();
+        assert_eq!(pool.price0_cumulative_last, cumulative(Decimal256::from_ratio(4u8, 1u8), 10));
+        assert_eq!(pool.price1_cumulative_last, cumulative(Decimal256::percent(25), 10));
+        assert_eq!(pool.block_time_last, 110);
+        assert_eq!(OBSERVATION_COUNT.load(&storage).unwrap(), 1);
+        let observation = OBSERVATIONS.load(&storage, 0).unwrap();
+        assert_eq!(observation.timestamp, 110);
+        assert_eq!((observation.price0_cumulative, observation.price1_cumulative), (pool.price0_cumulative_last, pool.price1_cumulative_last));
+
+        // a second update in the same block changes nothing
+        accumulate_prices(&mut storage, &env_at(110), &curve, &mut pool).unwrap();
+        assert_eq!(OBSERVATION_COUNT.load(&storage).unwrap(), 1);
+
+        // then 20 seconds at parity
+        pool.reserves = [Uint128::new(2_000), Uint128::new(2_000)];
+        accumulate_prices(&mut storage, &env_at(130), &curve, &mut pool).unwrap();
+        assert_eq!(pool.price0_cumulative_last, cumulative(Decimal256::from_ratio(4u8, 1u8), 10) + cumulative(Decimal256::one(), 20));
+        assert_eq!(pool.price1_cumulative_last, cumulative(Decimal256::percent(25), 10) + cumulative(Decimal256::one(), 20));
+        assert_eq!(OBSERVATION_COUNT.load(&storage).unwrap(), 2);
+
+        // an empty pool has no price and accumulates nothing
+        let mut empty = self::pool((0, 0), 130);
+        accumulate_prices(&mut storage, &env_at(140), &curve, &mut empty).unwrap();
+        assert_eq!((empty.price0_cumulative_last, empty.price1_cumulative_last), (Uint256::zero(), Uint256::zero()));
+    }
+
+    #[test]
+    fn cumulative_prices_at_interpolates_between_observations() {
+        let mut storage = MockStorage::new();
+        let curve = Curve::ConstantProduct;
+        let mut pool = pool((1_000, 4_000), 100);
+        accumulate_prices(&mut storage, &env_at(110), &curve, &mut pool).unwrap();
+        pool.reserves = [Uint128::new(2_000), Uint128::new(2_000)];
+        accumulate_prices(&mut storage, &env_at(130), &curve, &mut pool).unwrap();
+        pool.reserves = [Uint128::new(4_000), Uint128::new(1_000)];
+
+        let four = Decimal256::from_ratio(4u8, 1u8);
+        let quarter = Decimal256::percent(25);
+        // on an observation, between two of them, and after the last one with the current reserves
+        let at = |timestamp| cumulative_prices_at(&storage, &curve, &pool, timestamp).unwrap();
+        assert_eq!(at(110), (cumulative(four, 10), cumulative(quarter, 10)));
+        assert_eq!(at(125), (cumulative(four, 10) + cumulative(Decimal256::one(), 15), cumulative(quarter, 10) + cumulative(Decimal256::one(), 15)));
+        assert_eq!(at(130), (pool.price0_cumulative_last, pool.price1_cumulative_last));
+        assert_eq!(at(135), (pool.price0_cumulative_last + cumulative(quarter, 5), pool.price1_cumulative_last + cumulative(four, 5)));
+
+        let averages = average_prices(&storage, &curve, &pool, 110, 130).unwrap();
+        assert_eq!(averages, (Decimal256::one(), Decimal256::one()));
+        let averages = average_prices(&storage, &curve, &pool, 120, 140).unwrap();
+        assert_eq!(averages, (Decimal256::from_ratio(5u8, 8u8), Decimal256::from_ratio(5u8, 2u8)));
+
+        let err = cumulative_prices_at(&storage, &curve, &pool, 109).unwrap_err();
+        assert!(matches!(err, ContractError::ObservationTooOld { timestamp: 109, oldest: 110 }), "{err}");
+    }
+
+    #[test]
+    fn observations_wrap_around_the_buffer() {
+        let mut storage = MockStorage::new();
+        let curve = Curve::ConstantProduct;
+        // price of asset 0 between the observations k and k + 1, 10 seconds apart
+        let price = |k: u64| (k % 3 + 1) as u128;
+        let mut pool = pool((1_000, 1_000 * price(0)), 0);
+        let steps = OBSERVATION_BUFFER_SIZE + 5;
+        for k in 1..=steps {
+            accumulate_prices(&mut storage, &env_at(10 * k), &curve, &mut pool).unwrap();
+            pool.reserves[1] = Uint128::new(1_000 * price(k));
+        }
+        assert_eq!(OBSERVATION_COUNT.load(&storage).unwrap(), steps);
+
+        let expected = |timestamp: u64| {
+            let (steps, rest) = (timestamp / 10, timestamp % 10);
+            let mut sum = Uint256::zero();
+            for k in 0..steps {
+                sum += cumulative(Decimal256::from_ratio(price(k), 1u8), 10);
+            }
+            sum + cumulative(Decimal256::from_ratio(price(steps), 1u8), rest)
+        };
+        // the first 5 observations were overwritten by the newest ones
+        let oldest = 10 * 6;
+        for timestamp in [oldest, oldest + 5, oldest + 17, 10 * (steps - 3) + 4, 10 * steps - 1, 10 * steps] {
+            assert_eq!(cumulative_prices_at(&storage, &curve, &pool, timestamp).unwrap().0, expected(timestamp), "at {timestamp}");
+        }
+        let err = cumulative_prices_at(&storage, &curve, &pool, oldest - 1).unwrap_err();
+        assert!(matches!(err, ContractError::ObservationTooOld { timestamp, oldest: 60 } if timestamp == oldest - 1), "{err}");
+    }
+}
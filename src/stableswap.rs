@@ -0,0 +1,270 @@
+// Curve StableSwap invariant for two coins, A * n^n * sum(x) + D = A * D * n^n + D^(n+1) / (n^n * prod(x)),
+// solved with Newton's method as in the Curve contracts. `amp` is A * n^(n-1), the value Curve
+// stores. Both assets are assumed to have the same precision. As in math.rs, amounts paid out
+// are rounded down and amounts taken in rounded up.
+use cosmwasm_std::{Decimal256, Uint128, Uint256, Uint512};
+
+use crate::error::ContractError;
+use crate::math;
+
+const N_COINS: u8 = 2;
+const MAX_ITERATIONS: u8 = 255;
+
+// Bounds of the amplification, and how fast the owner can move it
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 1_000_000;
+pub const MAX_AMP_CHANGE: u64 = 10;
+pub const MIN_RAMP_TIME: u64 = 86_400;
+
+fn converged(a: Uint256, b: Uint256) -> bool {
+    let diff = if a > b { a - b } else { b - a };
+    diff <= Uint256::one()
+}
+
+// Invariant D of the reserves
+pub fn compute_d(amp: u64, reserves: &[Uint128; 2]) -> Result<Uint256, ContractError> {
+    let n = Uint256::from(N_COINS);
+    let sum = Uint256::from(reserves[0]).checked_add(Uint256::from(reserves[1]))?;
+    if sum.is_zero() {
+        return Ok(sum);
+    }
+    let ann = Uint256::from(amp).checked_mul(n)?;
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for reserve in reserves.iter() {
+            d_p = d_p.checked_mul(d)?.checked_div(Uint256::from(*reserve).checked_mul(n)?)?;
+        }
+        let d_prev = d;
+        let numerator = ann.checked_mul(sum)?.checked_add(d_p.checked_mul(n)?)?.checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(Uint256::one())?
+            .checked_mul(d)?
+            .checked_add(n.checked_add(Uint256::one())?.checked_mul(d_p)?)?;
+        d = numerator.checked_div(denominator)?;
+        if converged(d, d_prev) {
+            break;
+        }
+    }
+    Ok(d)
+}
+
+// Reserve of the other asset that keeps the invariant at `d` when one reserve is `reserve`
+pub fn compute_y(amp: u64, reserve: Uint128, d: Uint256) -> Result<Uint256, ContractError> {
+    let n = Uint256::from(N_COINS);
+    let ann = Uint256::from(amp).checked_mul(n)?;
+    let x = Uint256::from(reserve);
+
+    let c = d
+        .checked_mul(d)?
+        .checked_div(x.checked_mul(n)?)?
+        .checked_mul(d)?
+        .checked_div(ann.checked_mul(n)?)?;
+    let b = x.checked_add(d.checked_div(ann)?)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = y.checked_mul(n)?.checked_add(b)?.checked_sub(d)?;
+        y = y.checked_mul(y)?.checked_add(c)?.checked_div(denominator)?;
+        if converged(y, y_prev) {
+            break;
+        }
+    }
+    Ok(y)
+}
+
+// Output for `offer_amount` after fees
+pub fn swap_output(
+    amp: u64,
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = math::deduct_fee(offer_amount, fee_bps)?;
+    let d = compute_d(amp, &[offer_reserve, ask_reserve])?;
+    let new_ask_reserve = compute_y(amp, offer_reserve.checked_add(offer_net)?, d)?;
+    // one unit less, for the rounding of y
+    let output = Uint256::from(ask_reserve).saturating_sub(new_ask_reserve).saturating_sub(Uint256::one());
+    Ok(Uint128::try_from(output)?)
+}
+
+// Smallest offer whose output after fees is at least `ask_amount`. `ask_amount` must be below
+// `ask_reserve`.
+pub fn swap_input(
+    amp: u64,
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    ask_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let d = compute_d(amp, &[offer_reserve, ask_reserve])?;
+    // y is symmetric, the offer reserve needed once the ask reserve is down by ask_amount
+    let new_offer_reserve = compute_y(amp, ask_reserve.checked_sub(ask_amount)?, d)?;
+    let offer_net = new_offer_reserve
+        .checked_sub(Uint256::from(offer_reserve))?
+        .checked_add(Uint256::from(2u8))?;
+    math::mul_div_ceil(
+        Uint128::try_from(offer_net)?,
+        Uint128::from(math::BPS_DENOMINATOR),
+        Uint128::from(math::BPS_DENOMINATOR - fee_bps as u128),
+    )
+}
+
+// Marginal price of the base asset in the quote one, -dy/dx along the invariant for base reserve x
+// and quote reserve y: (Ann + D^3 / (4 x^2 y)) / (Ann + D^3 / (4 x y^2)), computed as
+// y / x * (4 Ann x^2 y + D^3) / (4 Ann x y^2 + D^3). Both reserves must be above zero.
+pub fn spot_price(amp: u64, base_reserve: Uint128, quote_reserve: Uint128) -> Result<Decimal256, ContractError> {
+    let d = Uint512::from(compute_d(amp, &[base_reserve, quote_reserve])?);
+    let ann = Uint512::from(amp).checked_mul(Uint512::from(N_COINS))?;
+    let (x, y) = (Uint512::from(base_reserve), Uint512::from(quote_reserve));
+
+    let d_cubed = d.checked_mul(d)?.checked_mul(d)?;
+    let four_ann_xy = Uint512::from(4u8).checked_mul(ann)?.checked_mul(x)?.checked_mul(y)?;
+    let numerator = four_ann_xy.checked_mul(x)?.checked_add(d_cubed)?;
+    let denominator = four_ann_xy.checked_mul(y)?.checked_add(d_cubed)?;
+    let curvature = numerator
+        .checked_mul(Uint512::from(Decimal256::one().atomics()))?
+        .checked_div(denominator)?;
+    let curvature = Decimal256::new(Uint256::try_from(curvature)?);
+    Ok(math::ratio(quote_reserve, base_reserve)?.checked_mul(curvature)?)
+}
+
+// Output for `offer_amount` after fees at the spot price, as if the reserves did not move
+pub fn spot_output(
+    amp: u64,
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = math::deduct_fee(offer_amount, fee_bps)?;
+    let price = spot_price(amp, offer_reserve, ask_reserve)?;
+    Ok(Uint128::try_from(Uint256::from(offer_net).checked_mul(price.atomics())?.checked_div(Decimal256::one().atomics())?)?)
+}
+
+// LP tokens for the first deposit: the invariant D of the deposit
+pub fn initial_shares(amp: u64, amounts: &[Uint128; 2]) -> Result<Uint128, ContractError> {
+    Ok(Uint128::try_from(compute_d(amp, amounts)?)?)
+}
+
+// Amplification at `time` while ramping linearly from `init_amp` at `init_amp_time` to
+// `next_amp` at `next_amp_time`
+pub fn current_amp(
+    init_amp: u64,
+    init_amp_time: u64,
+    next_amp: u64,
+    next_amp_time: u64,
+    time: u64,
+) -> u64 {
+    if time >= next_amp_time || next_amp_time <= init_amp_time {
+        return next_amp;
+    }
+    let elapsed = (time.saturating_sub(init_amp_time)) as u128;
+    let period = (next_amp_time - init_amp_time) as u128;
+    if next_amp >= init_amp {
+        init_amp + ((next_amp - init_amp) as u128 * elapsed / period) as u64
+    } else {
+        init_amp - ((init_amp - next_amp) as u128 * elapsed / period) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // Reference values from the get_D / get_y algorithm of the Curve StableSwap contracts,
+    // run with Python integers
+
+    #[test]
+    fn compute_d_matches_reference() {
+        let cases: [(u64, u128, u128, &str); 4] = [
+            (100, 1_000_000_000, 1_000_000_000, "2000000000"),
+            (100, 1_000_000_000, 500_000_000, "1499073492"),
+            (10, 123_456_789_000, 987_654_321_000, "1046068075469"),
+            (2000, 1_000_000_000_000_000_000_000_000, 1_001_000_000_000_000_000_000_000, "2000999999875124875148248"),
+        ];
+        for (amp, x0, x1, expected) in cases {
+            let d = compute_d(amp, &[Uint128::new(x0), Uint128::new(x1)]).unwrap();
+            assert_eq!(d.to_string(), expected, "amp {amp} reserves {x0} {x1}");
+        }
+    }
+
+    #[test]
+    fn compute_d_of_empty_pool_is_zero() {
+        assert_eq!(compute_d(100, &[Uint128::zero(), Uint128::zero()]).unwrap(), Uint256::zero());
+    }
+
+    #[test]
+    fn swap_output_matches_reference() {
+        let cases: [(u64, u128, u128, u128, u128); 4] = [
+            (100, 1_000_000_000, 1_000_000_000, 1_000_000, 999_990),
+            (100, 1_000_000_000, 1_000_000_000, 500_000_000, 496_752_702),
+            (10, 123_456_789_000, 987_654_321_000, 10_000_000_000, 16_902_708_299),
+            (2000, 1_000_000_000_000_000_000_000_000, 1_001_000_000_000_000_000_000_000, 10_000_000_000_000_000_000_000, 9_999_955_041_118_979_992_185),
+        ];
+        for (amp, offer_reserve, ask_reserve, offer_amount, expected) in cases {
+            let output = swap_output(amp, Uint128::new(offer_reserve), Uint128::new(ask_reserve), Uint128::new(offer_amount), 0).unwrap();
+            assert_eq!(output, Uint128::new(expected), "amp {amp} offer {offer_amount}");
+        }
+    }
+
+    #[test]
+    fn swap_output_takes_fee_from_offer() {
+        let reserve = Uint128::new(1_000_000_000);
+        let with_fee = swap_output(100, reserve, reserve, Uint128::new(1_000_000), 30).unwrap();
+        let offer_net = swap_output(100, reserve, reserve, Uint128::new(997_000), 0).unwrap();
+        assert_eq!(with_fee, offer_net);
+    }
+
+    #[test]
+    fn swap_input_covers_ask_amount() {
+        let cases: [(u64, u128, u128, u128); 3] = [
+            (100, 1_000_000_000, 1_000_000_000, 999_990),
+            (100, 1_000_000_000, 1_000_000_000, 496_752_702),
+            (10, 123_456_789_000, 987_654_321_000, 16_902_708_299),
+        ];
+        for (amp, offer_reserve, ask_reserve, ask_amount) in cases {
+            let (offer_reserve, ask_reserve, ask_amount) = (Uint128::new(offer_reserve), Uint128::new(ask_reserve), Uint128::new(ask_amount));
+            let offer = swap_input(amp, offer_reserve, ask_reserve, ask_amount, 30).unwrap();
+            assert!(swap_output(amp, offer_reserve, ask_reserve, offer, 30).unwrap() >= ask_amount);
+            // and no more than a few units above the smallest such offer
+            let less = offer.checked_sub(Uint128::new(5)).unwrap();
+            assert!(swap_output(amp, offer_reserve, ask_reserve, less, 30).unwrap() < ask_amount);
+        }
+    }
+
+    #[test]
+    fn spot_price_is_the_derivative_of_the_invariant() {
+        // balanced reserves trade at parity, whatever their size
+        for reserve in [1_000u128, 500_000, 1_000_000_000_000_000_000] {
+            assert_eq!(spot_price(100, Uint128::new(reserve), Uint128::new(reserve)).unwrap(), Decimal256::one());
+        }
+        // 1.00835153922504... for reserves 1:2 at amp 100, from the formula on Python integers
+        let price = spot_price(100, Uint128::new(1_000_000_000_000), Uint128::new(2_000_000_000_000)).unwrap();
+        assert!(price > Decimal256::from_str("1.008351539").unwrap() && price < Decimal256::from_str("1.008351540").unwrap(), "{price}");
+        let inverse = spot_price(100, Uint128::new(2_000_000_000_000), Uint128::new(1_000_000_000_000)).unwrap();
+        assert!(inverse.checked_mul(price).unwrap().abs_diff(Decimal256::one()) < Decimal256::from_str("0.000000000001").unwrap());
+    }
+
+    #[test]
+    fn spot_output_bounds_the_swap_output() {
+        // the curve gets worse as the reserves move, a swap returns at most its spot output
+        let (offer_reserve, ask_reserve) = (Uint128::new(1_000_000_000), Uint128::new(3_000_000_000));
+        let offer = Uint128::new(10_000_000);
+        let spot = spot_output(100, offer_reserve, ask_reserve, offer, 30).unwrap();
+        let output = swap_output(100, offer_reserve, ask_reserve, offer, 30).unwrap();
+        assert!(output < spot && spot - output < Uint128::new(10_000), "{output} {spot}");
+    }
+
+    #[test]
+    fn current_amp_ramps_linearly() {
+        assert_eq!(current_amp(100, 1_000, 200, 2_000, 500), 100);
+        assert_eq!(current_amp(100, 1_000, 200, 2_000, 1_500), 150);
+        assert_eq!(current_amp(100, 1_000, 200, 2_000, 3_000), 200);
+        assert_eq!(current_amp(200, 1_000, 100, 2_000, 1_250), 175);
+    }
+}
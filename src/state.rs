@@ -1,28 +1,126 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128, Uint256};
 use cw_storage_plus::{Item, Map};
+use std::fmt;
+
+use crate::asset::AssetInfo;
 
 #[cw_serde]
 pub struct LiquidityPool {
-    pub orai_reserve: Uint128,  // ORAI native token
-    pub usdt_reserve: Uint128,  // USDT CW20 token
+    pub reserves: [Uint128; 2], // Reserves, in the same order as ContractInfo.asset_infos
     pub total_shares: Uint128,    // Total liquidity shares
+    pub price0_cumulative_last: Uint256, // Sum of asset 0 price in asset 1 * seconds, Decimal256 atomics
+    pub price1_cumulative_last: Uint256, // Sum of asset 1 price in asset 0 * seconds, Decimal256 atomics
+    pub block_time_last: u64,            // Time of the last accumulator update, in seconds
+    pub weights: [Decimal; 2],           // Weights of the reserves, equal unless the pair is weighted
+}
+
+#[cw_serde]
+pub struct Observation {
+    pub timestamp: u64,
+    pub price0_cumulative: Uint256,
+    pub price1_cumulative: Uint256,
+}
+
+// Invariant the pair prices swaps with
+#[cw_serde]
+pub enum PairType {
+    // constant product, x * y = k
+    Xyk {},
+    // Curve StableSwap, for assets pegged to each other, see stableswap.rs
+    Stable {},
+    // Balancer weighted product, weights in the order of asset_infos, see weighted.rs
+    Weighted { weights: [Decimal; 2] },
 }
 
 #[cw_serde]
 pub struct ContractInfo {
-    pub owner: Addr, 
-    pub usdt_contract: String, 
-    pub lpt_contract: String, 
+    pub owner: Addr,
+    pub asset_infos: [AssetInfo; 2],
+    pub lpt_contract: String,
+    pub lp_fee_bps: u16,       // Part of the swap fee left in the pool
+    pub protocol_fee_bps: u16, // Part of the swap fee sent to fee_collector
+    pub fee_collector: Option<Addr>,
+    pub pair_type: PairType,
+    pub guardian: Option<Addr>, // Can set the pause mode besides the owner
+}
+
+impl ContractInfo {
+    pub fn total_fee_bps(&self) -> u16 {
+        self.lp_fee_bps + self.protocol_fee_bps
+    }
 }
 // Storage for the liquidity pool
 pub const POOL: Item<LiquidityPool> = Item::new("pool");
 
-// Mapping from user address to their liquidity shares
-pub const LIQUIDITY_PROVIDERS: Map<&Addr, Uint128> = Map::new("liquidity_providers");
+// cw20 tokens sent through the ProvideLiquidity hook and not yet used, keyed by (user, token contract)
+pub const DEPOSITS: Map<(&Addr, &str), Uint128> = Map::new("deposits");
+
+// Ring buffer of price accumulator observations, see oracle.rs
+pub const OBSERVATIONS: Map<u64, Observation> = Map::new("observations");
+
+// Number of observations ever written, the next one goes to slot count % buffer size
+pub const OBSERVATION_COUNT: Item<u64> = Item::new("observation_count");
+
+// Amplification of a stable pair, ramping linearly from init_amp to next_amp
+#[cw_serde]
+pub struct AmpConfig {
+    pub init_amp: u64,
+    pub init_amp_time: u64,
+    pub next_amp: u64,
+    pub next_amp_time: u64,
+}
+
+// Only set for stable pairs
+pub const AMP_CONFIG: Item<AmpConfig> = Item::new("amp_config");
+
+//storage for the contract info, off the "contract_info" key that cw2 keeps the version under
+pub const INFO: Item<ContractInfo> = Item::new("pair_info");
+
+// Ownership transfer proposed by the owner, waiting for the new owner to claim it
+#[cw_serde]
+pub struct OwnershipProposal {
+    pub owner: Addr,
+    pub expires_at: Timestamp,
+}
+
+pub const OWNERSHIP_PROPOSAL: Item<OwnershipProposal> = Item::new("ownership_proposal");
+
+// Operations stopped by the owner or the guardian, withdrawals stay open unless in Emergency
+#[cw_serde]
+pub enum PauseMode {
+    Unpaused {},
+    Swaps {},
+    Deposits {},
+    AllExceptWithdrawals {},
+    Emergency {},
+}
+
+impl PauseMode {
+    pub fn blocks_swaps(&self) -> bool {
+        matches!(self, PauseMode::Swaps {} | PauseMode::AllExceptWithdrawals {} | PauseMode::Emergency {})
+    }
+
+    pub fn blocks_deposits(&self) -> bool {
+        matches!(self, PauseMode::Deposits {} | PauseMode::AllExceptWithdrawals {} | PauseMode::Emergency {})
+    }
 
-//storage for the contract info 
-pub const INFO: Item<ContractInfo> = Item::new("contract_info");
+    pub fn blocks_withdrawals(&self) -> bool {
+        matches!(self, PauseMode::Emergency {})
+    }
+}
+
+impl fmt::Display for PauseMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PauseMode::Unpaused {} => write!(f, "unpaused"),
+            PauseMode::Swaps {} => write!(f, "swaps"),
+            PauseMode::Deposits {} => write!(f, "deposits"),
+            PauseMode::AllExceptWithdrawals {} => write!(f, "all_except_withdrawals"),
+            PauseMode::Emergency {} => write!(f, "emergency"),
+        }
+    }
+}
 
-//allowance amount usdt
-pub const USDT_ALLOWANCE: Map<&Addr, Uint128> = Map::new("usdt_allowance");
\ No newline at end of file
+// Unpaused when not set
+pub const PAUSE_MODE: Item<PauseMode> = Item::new("pause_mode");
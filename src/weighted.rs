@@ -0,0 +1,282 @@
+// Balancer weighted product invariant, prod(reserve_i ^ weight_i) = k, for two assets whose
+// weights add up to 1. Fractional powers are approximated on Decimal256, the approximation is
+// padded by POW_PRECISION in favor of the pool, and as in math.rs amounts paid out are rounded
+// down and amounts taken in rounded up.
+use cosmwasm_std::{Decimal, Decimal256, Uint128, Uint256};
+
+use crate::error::ContractError;
+use crate::math;
+
+// Bound on the error of `pow`, 1e-10
+const POW_PRECISION: Decimal256 = Decimal256::raw(100_000_000);
+const MAX_POW_ITERATIONS: u32 = 256;
+
+// Largest trade against the reserves, as in Balancer: offers up to half the offer reserve,
+// outputs up to 30% of the ask reserve
+pub const MAX_IN_RATIO: Decimal = Decimal::percent(50);
+pub const MAX_OUT_RATIO: Decimal = Decimal::percent(30);
+
+// Bounds of a single weight
+pub const MIN_WEIGHT: Decimal = Decimal::percent(2);
+pub const MAX_WEIGHT: Decimal = Decimal::percent(98);
+
+fn one() -> Decimal256 {
+    Decimal256::one()
+}
+
+// base ^ exp for 0 < base, through the binomial series of the fractional part of exp. The base
+// is square rooted into [0.5, 1.5] first, where the series converges quickly.
+pub fn pow(base: Decimal256, exp: Decimal256) -> Result<Decimal256, ContractError> {
+    if base.is_zero() {
+        return Ok(Decimal256::zero());
+    }
+    let half = Decimal256::percent(50);
+    let (mut base, mut exp) = (base, exp);
+    while base < half || base > one() + half {
+        base = base.sqrt();
+        exp = exp.checked_mul(Decimal256::from_ratio(2u8, 1u8))?;
+    }
+
+    let whole = exp.to_uint_floor();
+    let frac = exp - Decimal256::from_ratio(whole, 1u8);
+    let whole_exp = u32::try_from(Uint128::try_from(whole)?.u128()).map_err(|_| ContractError::InvalidWeights {})?;
+    let whole_pow = base.checked_pow(whole_exp)?;
+    if frac.is_zero() {
+        return Ok(whole_pow);
+    }
+
+    // (1 + x) ^ frac = sum over k of C(frac, k) * x^k, with the sign of each term tracked apart
+    let (x, x_negative) = if base >= one() { (base - one(), false) } else { (one() - base, true) };
+    let mut term = one();
+    let mut sum = one();
+    let mut negative = false;
+    for k in 1..=MAX_POW_ITERATIONS {
+        let big_k = Decimal256::from_ratio(k, 1u8);
+        let k_minus_one = big_k - one();
+        let (c, c_negative) = if frac >= k_minus_one { (frac - k_minus_one, false) } else { (k_minus_one - frac, true) };
+        term = term.checked_mul(c.checked_mul(x)?)?.checked_div(big_k)?;
+        if term.is_zero() {
+            break;
+        }
+        if x_negative {
+            negative = !negative;
+        }
+        if c_negative {
+            negative = !negative;
+        }
+        sum = if negative { sum.checked_sub(term)? } else { sum.checked_add(term)? };
+        if term < POW_PRECISION / Decimal256::from_ratio(100u8, 1u8) {
+            break;
+        }
+    }
+    Ok(whole_pow.checked_mul(sum)?)
+}
+
+fn mul_floor(amount: Uint128, ratio: Decimal256) -> Result<Uint128, ContractError> {
+    let result = Uint256::from(amount)
+        .checked_mul(ratio.atomics())?
+        .checked_div(Decimal256::one().atomics())?;
+    Ok(Uint128::try_from(result)?)
+}
+
+fn mul_ceil(amount: Uint128, ratio: Decimal256) -> Result<Uint128, ContractError> {
+    let numerator = Uint256::from(amount).checked_mul(ratio.atomics())?;
+    let denominator = Decimal256::one().atomics();
+    let mut result = numerator.checked_div(denominator)?;
+    if !numerator.checked_rem(denominator)?.is_zero() {
+        result = result.checked_add(Uint256::one())?;
+    }
+    Ok(Uint128::try_from(result)?)
+}
+
+fn weight_ratio(numerator: Decimal, denominator: Decimal) -> Result<Decimal256, ContractError> {
+    Ok(Decimal256::from(numerator).checked_div(Decimal256::from(denominator))?)
+}
+
+// Whether amount / reserve is above max_ratio. Ratios too large for a Decimal, and any amount
+// against an empty reserve, are above it.
+fn exceeds_ratio(amount: Uint128, reserve: Uint128, max_ratio: Decimal) -> bool {
+    Decimal::checked_from_ratio(amount, reserve).map_or(true, |ratio| ratio > max_ratio)
+}
+
+pub fn assert_weights(weights: &[Decimal; 2]) -> Result<(), ContractError> {
+    let in_bounds = weights.iter().all(|weight| (MIN_WEIGHT..=MAX_WEIGHT).contains(weight));
+    if !in_bounds || weights[0] + weights[1] != Decimal::one() {
+        return Err(ContractError::InvalidWeights {});
+    }
+    Ok(())
+}
+
+// Output for `offer_amount` after fees: ask_reserve * (1 - (offer_reserve / (offer_reserve + offer_net)) ^ (offer_weight / ask_weight))
+pub fn swap_output(
+    offer_reserve: Uint128,
+    offer_weight: Decimal,
+    ask_reserve: Uint128,
+    ask_weight: Decimal,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = math::deduct_fee(offer_amount, fee_bps)?;
+    if exceeds_ratio(offer_net, offer_reserve, MAX_IN_RATIO) {
+        return Err(ContractError::MaxTradeRatioExceeded {});
+    }
+    let base = math::ratio(offer_reserve, offer_reserve.checked_add(offer_net)?)?;
+    let power = pow(base, weight_ratio(offer_weight, ask_weight)?)?.checked_add(POW_PRECISION)?;
+    if power >= one() {
+        return Ok(Uint128::zero());
+    }
+    let output = mul_floor(ask_reserve, one() - power)?;
+    if exceeds_ratio(output, ask_reserve, MAX_OUT_RATIO) {
+        return Err(ContractError::MaxTradeRatioExceeded {});
+    }
+    Ok(output)
+}
+
+// Smallest offer whose output after fees is at least `ask_amount`:
+// offer_reserve * ((ask_reserve / (ask_reserve - ask_amount)) ^ (ask_weight / offer_weight) - 1), before fees
+pub fn swap_input(
+    offer_reserve: Uint128,
+    offer_weight: Decimal,
+    ask_reserve: Uint128,
+    ask_weight: Decimal,
+    ask_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    if exceeds_ratio(ask_amount, ask_reserve, MAX_OUT_RATIO) {
+        return Err(ContractError::MaxTradeRatioExceeded {});
+    }
+    // the power of the inverse ratio keeps the base below 1, it is padded down so the offer rounds up
+    let base = math::ratio(ask_reserve.checked_sub(ask_amount)?, ask_reserve)?;
+    let power = pow(base, weight_ratio(ask_weight, offer_weight)?)?.saturating_sub(POW_PRECISION);
+    let growth = one().checked_div(power)?.checked_sub(one())?;
+    let offer_net = mul_ceil(offer_reserve, growth)?;
+    math::mul_div_ceil(
+        offer_net,
+        Uint128::from(math::BPS_DENOMINATOR),
+        Uint128::from(math::BPS_DENOMINATOR - fee_bps as u128),
+    )
+}
+
+// Price of the ask asset in the offer asset: (offer_reserve / offer_weight) / (ask_reserve / ask_weight)
+pub fn spot_price(
+    offer_reserve: Uint128,
+    offer_weight: Decimal,
+    ask_reserve: Uint128,
+    ask_weight: Decimal,
+) -> Result<Decimal256, ContractError> {
+    Ok(math::ratio(offer_reserve, ask_reserve)?.checked_mul(weight_ratio(ask_weight, offer_weight)?)?)
+}
+
+// Output of `offer_amount` after fees at the spot price, i.e. without price impact
+pub fn spot_output(
+    offer_reserve: Uint128,
+    offer_weight: Decimal,
+    ask_reserve: Uint128,
+    ask_weight: Decimal,
+    offer_amount: Uint128,
+    fee_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let offer_net = math::deduct_fee(offer_amount, fee_bps)?;
+    let price = spot_price(offer_reserve, offer_weight, ask_reserve, ask_weight)?;
+    let output = Uint256::from(offer_net)
+        .checked_mul(Decimal256::one().atomics())?
+        .checked_div(price.atomics())?;
+    Ok(Uint128::try_from(output)?)
+}
+
+// LP tokens for the first deposit: the invariant amount_0 ^ weight_0 * amount_1 ^ weight_1, computed
+// as the larger amount times the power of the ratio of the two, which is below 1
+pub fn initial_shares(amounts: &[Uint128; 2], weights: &[Decimal; 2]) -> Result<Uint128, ContractError> {
+    let (large, small, small_weight) = if amounts[0] >= amounts[1] {
+        (amounts[0], amounts[1], weights[1])
+    } else {
+        (amounts[1], amounts[0], weights[0])
+    };
+    let power = pow(math::ratio(small, large)?, Decimal256::from(small_weight))?.saturating_sub(POW_PRECISION);
+    mul_floor(large, power)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal256 {
+        Decimal256::from_str(value).unwrap()
+    }
+
+    fn assert_close(actual: Decimal256, expected: Decimal256) {
+        let diff = if actual > expected { actual - expected } else { expected - actual };
+        assert!(diff <= POW_PRECISION, "{actual} is not within {POW_PRECISION} of {expected}");
+    }
+
+    // Reference values computed with Python's decimal module at 40 digits of precision
+
+    #[test]
+    fn pow_matches_reference() {
+        assert_close(pow(dec("0.5"), dec("0.5")).unwrap(), dec("0.707106781186547524"));
+        assert_close(pow(dec("0.9"), dec("4")).unwrap(), dec("0.6561"));
+        assert_close(pow(dec("0.9"), dec("0.25")).unwrap(), dec("0.974003746425296764"));
+        assert_close(pow(dec("0.8"), dec("1.5")).unwrap(), dec("0.715541752799932702"));
+        assert_close(pow(dec("0.01"), dec("0.2")).unwrap(), dec("0.398107170553497250"));
+        assert_close(pow(dec("1.2"), dec("0.3")).unwrap(), dec("1.056219968439258170"));
+        assert_close(pow(dec("3"), dec("0.5")).unwrap(), dec("1.732050807568877293"));
+    }
+
+    #[test]
+    fn equal_weights_match_constant_product() {
+        let half = Decimal::percent(50);
+        let reserve = Uint128::new(1_000_000_000);
+        let output = swap_output(reserve, half, reserve, half, Uint128::new(10_000_000), 0).unwrap();
+        let xyk = math::swap_output(reserve, reserve, Uint128::new(10_000_000), 0).unwrap();
+        assert!(output <= xyk && xyk - output <= Uint128::new(1));
+    }
+
+    #[test]
+    fn swap_output_matches_reference() {
+        // 80/20 pool, 1_000_000 of the 80% asset against 4_000_000 of the 20% one: 1 - (1 / 1.01) ^ 4
+        let output = swap_output(
+            Uint128::new(1_000_000),
+            Decimal::percent(80),
+            Uint128::new(4_000_000),
+            Decimal::percent(20),
+            Uint128::new(10_000),
+            0,
+        )
+        .unwrap();
+        assert_eq!(output, Uint128::new(156_078));
+    }
+
+    #[test]
+    fn swap_input_covers_ask_amount() {
+        let (offer_reserve, ask_reserve) = (Uint128::new(1_000_000), Uint128::new(4_000_000));
+        let (offer_weight, ask_weight) = (Decimal::percent(80), Decimal::percent(20));
+        let ask_amount = Uint128::new(156_078);
+        let offer = swap_input(offer_reserve, offer_weight, ask_reserve, ask_weight, ask_amount, 30).unwrap();
+        assert!(swap_output(offer_reserve, offer_weight, ask_reserve, ask_weight, offer, 30).unwrap() >= ask_amount);
+    }
+
+    #[test]
+    fn oversized_trades_are_rejected() {
+        // offer / reserve is beyond the range of a Decimal
+        let half = Decimal::percent(50);
+        let err = swap_output(Uint128::new(1), half, Uint128::new(1_000), half, Uint128::MAX, 0).unwrap_err();
+        assert!(matches!(err, ContractError::MaxTradeRatioExceeded {}));
+        let err = swap_input(Uint128::new(1_000), half, Uint128::new(1), half, Uint128::MAX, 0).unwrap_err();
+        assert!(matches!(err, ContractError::MaxTradeRatioExceeded {}));
+    }
+
+    #[test]
+    fn spot_price_accounts_for_weights() {
+        // (1_000_000 / 0.8) / (4_000_000 / 0.2) = 1 / 16
+        let price = spot_price(Uint128::new(1_000_000), Decimal::percent(80), Uint128::new(4_000_000), Decimal::percent(20)).unwrap();
+        assert_eq!(price, dec("0.0625"));
+    }
+
+    #[test]
+    fn initial_shares_is_weighted_geometric_mean() {
+        // 1_000_000 ^ 0.8 * 4_000_000 ^ 0.2 = 1_000_000 * 4 ^ 0.2
+        let shares = initial_shares(&[Uint128::new(1_000_000), Uint128::new(4_000_000)], &[Decimal::percent(80), Decimal::percent(20)]).unwrap();
+        assert_eq!(shares, Uint128::new(1_319_507));
+    }
+}